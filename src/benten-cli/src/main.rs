@@ -1,5 +1,9 @@
 use structopt::StructOpt;
 use structopt::clap::AppSettings;
+use std::path::PathBuf;
+use std::io::{ BufRead, BufReader };
+use std::os::unix::net::UnixStream;
+use benten_ipc::StatusEvent;
 
 pub fn main() {
     match Arguments::from_args().command {
@@ -23,6 +27,31 @@ pub fn main() {
             println!("{}", std::fs::read_to_string(&file_path).unwrap());
         },
 
+        Command::Watch{json} => {
+            let path = benten_ipc::status::socket_path().unwrap_or_else(|| {
+                eprintln!("XDG_RUNTIME_DIR is not set, benten does not serve its status without it");
+                std::process::exit(1);
+            });
+            let stream = UnixStream::connect(&path).unwrap_or_else(|e| {
+                eprintln!("could not connect to {}, is benten running ? ({})", path.display(), e);
+                std::process::exit(1);
+            });
+
+            for line in BufReader::new(stream).lines() {
+                let event = match StatusEvent::decode(&line.unwrap()) {
+                    Some(event) => event,
+                    None => continue,
+                };
+
+                match (json, event) {
+                    (true, event) => println!("{}", event.to_json()),
+                    (false, StatusEvent::Method { layout, method, label }) => println!("{} {} {}", layout, method, label),
+                    (false, StatusEvent::Active(active)) => println!("{}", if active { "active" } else { "inactive" }),
+                    (false, StatusEvent::Preedit(text)) => println!("preedit {}", text),
+                }
+            }
+        },
+
         Command::List => {
             let base_dir = xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home(); 
 
@@ -33,6 +62,80 @@ pub fn main() {
                 println!("  {}", path.unwrap().file_name().to_str().unwrap().split('.').next().unwrap())           
             }
         },
+
+        Command::ImportXkb{path, section, id} => {
+            let id = id.unwrap_or_else(|| section.clone().unwrap_or_else(|| path.file_stem().unwrap().to_str().unwrap().to_string()));
+            let (layout, skipped) = benten::import::import_xkb(&path, section.as_deref(), &id).unwrap_or_else(|e| {
+                eprintln!("could not import {}: {}", path.display(), e);
+                std::process::exit(1);
+            });
+
+            for skipped in skipped {
+                eprintln!("skipped {}", skipped);
+            }
+
+            let base_dir = xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home();
+            let file_path = base_dir.join("layouts").join(&id).with_extension("layout.zm");
+            if file_path.exists() {
+                eprintln!("layout `{}` already exists at {}", id, file_path.display());
+                std::process::exit(1);
+            }
+
+            std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            std::fs::write(&file_path, layout.to_zm()).unwrap();
+            println!("{}", file_path.display());
+        },
+
+        Command::ImportTable{path, format, id} => {
+            let id = id.unwrap_or_else(|| path.file_name().unwrap().to_str().unwrap().split('.').next().unwrap().to_string());
+            let entries = benten::import::import_table(&path, format).unwrap();
+
+            let base_dir = xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home();
+            let file_path = base_dir.join("tables").join(&id).with_extension("dict");
+            if file_path.exists() {
+                eprintln!("table `{}` already exists at {}", id, file_path.display());
+                std::process::exit(1);
+            }
+
+            std::fs::create_dir_all(file_path.parent().unwrap()).unwrap();
+            benten::import::write_dict(&entries, &file_path).unwrap();
+            println!("{} ({} entries)", file_path.display(), entries.len());
+        },
+
+        Command::ResetHistory{table} => {
+            let history_path = benten::History::path(&table);
+            if history_path.exists() {
+                std::fs::remove_file(history_path).unwrap();
+            }
+
+            //reload so that the running engine forgets the counts as well
+            let file_path = xdg::BaseDirectories::with_prefix("benten").unwrap().get_data_home().join("current_layout");
+            if let Ok(current_layout) = std::fs::read_to_string(&file_path) {
+                std::fs::write(file_path, current_layout).unwrap();
+            }
+        },
+
+        Command::Lookup{value, table} => {
+            let base_dir = xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home();
+            match table {
+                Some(table) => {
+                    let table = benten::Table::from_path(&table, &base_dir).unwrap();
+                    println!("{}", table.reverse_lookup(&value).join(" "));
+                },
+
+                //every table which has the value
+                None => {
+                    let paths = std::fs::read_dir(base_dir.join("tables")).unwrap();
+                    for path in paths.map(|path| path.unwrap().path()).filter(|path| path.extension().is_some_and(|ext| ext=="dict")) {
+                        let id = path.file_stem().unwrap().to_str().unwrap();
+                        let sequences = benten::Table::from_path(id, &base_dir).unwrap().reverse_lookup(&value);
+                        if !sequences.is_empty() {
+                            println!("{}\t{}", id, sequences.join(" "));
+                        }
+                    }
+                },
+            }
+        },
     };
 }
 
@@ -70,4 +173,49 @@ pub enum Command {
     #[structopt(alias = "c", no_version, global_settings = &[AppSettings::DisableVersion])]
     ///Current layout
     Current,      
+
+    #[structopt(alias = "w", no_version, global_settings = &[AppSettings::DisableVersion])]
+    ///Print the layout, method, activation and preedit of the running engine as they change
+    Watch {
+        #[structopt(long="json")]
+        ///Print each change as a line of JSON
+        json: bool,
+    },
+
+    #[structopt(no_version, global_settings = &[AppSettings::DisableVersion])]
+    ///Import a layout from a section of an XKB symbols file
+    ImportXkb { 
+        path: PathBuf,
+        #[structopt(short="s", long="section")]
+        ///Section of the file, defaults to the one marked `default`
+        section: Option<String>,
+        #[structopt(long="id")]
+        ///Id of the imported layout, defaults to the section name
+        id: Option<String>,
+    },
+
+    #[structopt(no_version, global_settings = &[AppSettings::DisableVersion])]
+    ///Import a table from an IBus, fcitx or RIME dictionary
+    ImportTable {
+        path: PathBuf,
+        #[structopt(short="f", long="format")]
+        ///Format of the dictionary: ibus, fcitx or rime
+        format: benten::import::TableFormat,
+        #[structopt(long="id")]
+        ///Id of the imported table, defaults to the file name without extensions
+        id: Option<String>,
+    },
+
+    #[structopt(no_version, global_settings = &[AppSettings::DisableVersion])]
+    ///Forget the characters learned for a table
+    ResetHistory { table: String },
+
+    #[structopt(no_version, global_settings = &[AppSettings::DisableVersion])]
+    ///Show the key sequences producing a character or phrase
+    Lookup {
+        value: String,
+        #[structopt(short="t", long="table")]
+        ///Table to look in, defaults to every table
+        table: Option<String>,
+    },
 }
\ No newline at end of file
@@ -2,6 +2,9 @@ use inotify_sys as ffi;
 use std::{collections::HashMap, ffi::CString, os::raw::c_int, path::PathBuf};
 use std::sync::mpsc::Sender;
 
+pub mod status;
+pub use status::{ StatusEvent, StatusSocket };
+
 
 
 
@@ -81,6 +84,12 @@ struct WatchState {
     watchers: Vec<Sender<EventMask>>,
 }
 
+impl Default for Inotify {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Inotify {
     pub fn new() -> Self {
         let flags = ffi::IN_NONBLOCK;
@@ -206,6 +215,7 @@ mod iterator {
     };
 
     #[derive(Debug)]
+    #[allow(dead_code)] // read as a whole from the inotify fd
     pub struct RawEvent {
         pub wd: c_int,
         pub mask: u32,
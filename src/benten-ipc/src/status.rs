@@ -0,0 +1,197 @@
+use std::io::{ ErrorKind, Write };
+use std::os::unix::net::{ UnixListener, UnixStream };
+use std::os::unix::io::AsRawFd;
+use std::os::unix::fs::{ DirBuilderExt, PermissionsExt };
+use std::path::PathBuf;
+
+/// Changes streamed to status clients, one per line with its fields separated by tabs
+#[derive(Debug, Clone, PartialEq)]
+pub enum StatusEvent {
+    Method { layout: String, method: String, label: String },
+    Active(bool),
+    Preedit(String),
+}
+
+/// `$XDG_RUNTIME_DIR/benten/status.sock`, none without a runtime directory as any other is shared between users
+pub fn socket_path() -> Option<PathBuf> {
+    let runtime_dir = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty())?;
+    Some(PathBuf::from(runtime_dir).join("benten").join("status.sock"))
+}
+
+impl StatusEvent {
+    pub fn encode(&self) -> String {
+        let fields = match self {
+            StatusEvent::Method { layout, method, label } => vec!["method", layout, method, label],
+            StatusEvent::Active(active) => vec!["active", if *active { "true" } else { "false" }],
+            StatusEvent::Preedit(text) => vec!["preedit", text],
+        };
+
+        let fields: Vec<String> = fields.into_iter().map(escape).collect();
+        fields.join("\t")
+    }
+
+    pub fn decode(line: &str) -> Option<Self> {
+        let fields: Vec<String> = line.split('\t').map(unescape).collect();
+        match fields.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+            ["method", layout, method, label] => Some(StatusEvent::Method {
+                layout: layout.to_string(),
+                method: method.to_string(),
+                label: label.to_string(),
+            }),
+            ["active", active] => Some(StatusEvent::Active(*active=="true")),
+            ["preedit", text] => Some(StatusEvent::Preedit(text.to_string())),
+            _ => None,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        match self {
+            StatusEvent::Method { layout, method, label } => format!(
+                r#"{{"event":"method","layout":{},"method":{},"label":{}}}"#,
+                json_string(layout), json_string(method), json_string(label)
+            ),
+            StatusEvent::Active(active) => format!(r#"{{"event":"active","active":{}}}"#, active),
+            StatusEvent::Preedit(text) => format!(r#"{{"event":"preedit","text":{}}}"#, json_string(text)),
+        }
+    }
+}
+
+fn escape(field: &str) -> String {
+    field.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::with_capacity(field.len());
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('t') => out.push('\t'),
+                Some('n') => out.push('\n'),
+                Some(c) => out.push(c),
+                None => {},
+            },
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+fn json_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32)<0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+    out
+}
+
+/// Socket status clients such as `benten-cli watch` connect to, every client being sent each event
+pub struct StatusSocket {
+    listener: UnixListener,
+    clients: Vec<UnixStream>,
+}
+
+impl StatusSocket {
+    /// Listens at `socket_path()`, replacing the socket left by a previous server
+    pub fn bind() -> std::io::Result<Self> {
+        let path = socket_path().ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, "XDG_RUNTIME_DIR is not set"))?;
+        let dir = path.parent().unwrap();
+        std::fs::DirBuilder::new().recursive(true).mode(0o700).create(dir)?;
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+
+        let listener = UnixListener::bind(&path)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener, clients: Vec::new() })
+    }
+
+    /// Accepts the clients waiting to connect, sending them `current` first
+    pub fn accept(&mut self, current: &[StatusEvent]) {
+        loop {
+            match self.listener.accept() {
+                Ok((mut client, _)) => {
+                    if client.set_nonblocking(true).is_err() {
+                        continue;
+                    }
+
+                    if current.iter().all(|event| send(&mut client, event)) {
+                        self.clients.push(client);
+                    }
+                },
+
+                Err(e) => {
+                    if e.kind()!=ErrorKind::WouldBlock {
+                        eprintln!("status socket: {}", e);
+                    }
+
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Sends `event` to every client, dropping those which disconnected or do not keep up
+    pub fn send(&mut self, event: &StatusEvent) {
+        self.clients.retain_mut(|client| send(client, event));
+    }
+}
+
+fn send(client: &mut UnixStream, event: &StatusEvent) -> bool {
+    writeln!(client, "{}", event.encode()).is_ok()
+}
+
+impl mio::event::Source for StatusSocket {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.listener.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.listener.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> std::io::Result<()> {
+        mio::unix::SourceFd(&self.listener.as_raw_fd()).deregister(registry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let events = [
+            StatusEvent::Method { layout: "japanese".to_string(), method: "Kana".to_string(), label: "かな".to_string() },
+            StatusEvent::Active(false),
+            StatusEvent::Preedit("a\tb\\n\n".to_string()),
+        ];
+
+        for event in &events {
+            assert_eq!(StatusEvent::decode(&event.encode()).as_ref(), Some(event));
+        }
+
+        assert_eq!(events[2].to_json(), r#"{"event":"preedit","text":"a\tb\\n\n"}"#);
+    }
+}
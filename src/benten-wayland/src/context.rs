@@ -1,4 +1,5 @@
-use benten::{ BentenEngine, BentenResponse, BentenConfig };
+use benten::{ BentenEngine, BentenResponse, BentenConfig, BentenEvent, Function };
+use benten_ipc::{ StatusEvent, StatusSocket };
 use std::time::{ Duration, Instant };
 use mio_timerfd::TimerFd;
 
@@ -20,9 +21,16 @@ pub struct BentenContext {
     mod_state: bool,
     serial: u32,
     timer: TimerFd,
+    label_timer: TimerFd,
     repeat_state: Option<(RepeatInfo, PressState)>,
+    status: Option<StatusSocket>,
+    preedit: String, //last sent to the input method
+    label_shown: bool, //the preedit is the label of the method switched to, nothing being typed
 }
 
+/// How long the label of the method switched to stays in the preedit
+const LABEL_DURATION: Duration = Duration::from_millis(800);
+
 // Global modifiers that should not be handled in the engine, maybe define them in .layout.yaml ?
 pub enum ModifierState {
     CONTROL = 0x4,
@@ -64,12 +72,9 @@ impl PressState {
 }
 
 impl BentenContext {
-    pub fn new(layout: &str, vk: Main<ZwpVirtualKeyboardV1>, im: Main<ZwpInputMethodV2>, timer: TimerFd) -> Self { 
+    pub fn new(layout: &str, vk: Main<ZwpVirtualKeyboardV1>, im: Main<ZwpInputMethodV2>, timer: TimerFd, label_timer: TimerFd, status: Option<StatusSocket>) -> Self { 
         Self {
-            engine: BentenEngine::new(BentenConfig { 
-                id: String::from(layout),
-                ..BentenConfig::default() 
-            }),
+            engine: BentenEngine::new(BentenConfig::load(layout)),
             current_state: InputMethodState::Inactive,
             serial: 0,
             keymap_init: false,
@@ -77,10 +82,78 @@ impl BentenContext {
             vk,
             im,
             timer,
+            label_timer,
             repeat_state: None,
+            status,
+            preedit: String::new(),
+            label_shown: false,
+        }
+    }
+
+    /// Accepts new status clients, telling them the current state of the engine
+    pub fn accept_status_clients(&mut self) {
+        let current = [
+            StatusEvent::Method {
+                layout: self.engine.layout().to_string(),
+                method: self.engine.current_method().to_string(),
+                label: self.engine.label().to_string(),
+            },
+            StatusEvent::Active(self.current_state==InputMethodState::Active),
+            StatusEvent::Preedit(self.typed().to_string()),
+        ];
+
+        if let Some(status) = &mut self.status {
+            status.accept(&current);
         }
     }
 
+    /// Sends the events of the engine to status clients, returns the label of the last method switched to
+    pub fn publish_events(&mut self) -> Option<String> {
+        let mut last_label = None;
+        for event in self.engine.events() {
+            match event {
+                BentenEvent::MethodChanged { layout, method, label } => {
+                    self.send_status(StatusEvent::Method { layout, method, label: label.clone() });
+                    last_label = Some(label);
+                },
+            }
+        }
+
+        last_label
+    }
+
+    /// Sends `event` to status clients, if the status socket could be bound
+    fn send_status(&mut self, event: StatusEvent) {
+        if let Some(status) = &mut self.status {
+            status.send(&event);
+        }
+    }
+
+    /// What is being typed, as sent to status clients
+    fn typed(&self) -> &str {
+        if self.label_shown { "" } else { &self.preedit }
+    }
+
+    fn set_preedit(&mut self, text: String, cursor_begin: i32, cursor_end: i32) {
+        self.update_preedit(text, cursor_begin, cursor_end, false);
+    }
+
+    /// Sends `text` to the input method, and to status clients when it is typed rather than a label
+    fn update_preedit(&mut self, text: String, cursor_begin: i32, cursor_end: i32, label: bool) {
+        let typed = if label { "" } else { text.as_str() };
+        if typed!=self.typed() {
+            self.send_status(StatusEvent::Preedit(typed.to_string()));
+        }
+
+        if self.label_shown && !label {
+            self.label_timer.disarm().unwrap();
+        }
+
+        self.label_shown = label;
+        self.preedit = text.clone();
+        self.im.set_preedit_string(text, cursor_begin, cursor_end);
+    }
+
     pub fn new_data<'a>(data: &'a mut DispatchData) -> &'a mut Self {
         data.get::<Self>().unwrap()
     }
@@ -88,11 +161,13 @@ impl BentenContext {
     pub fn handle_im_ev(&mut self, ev: ImEvent) {
         match ev {
             ImEvent::Activate => {
-                self.current_state = InputMethodState::Active
+                self.current_state = InputMethodState::Active;
+                self.send_status(StatusEvent::Active(true));
             },
 
             ImEvent::Deactivate => {
                 self.current_state = InputMethodState::Inactive;
+                self.send_status(StatusEvent::Active(false));
             },
 
             ImEvent::Unavailable => {
@@ -103,7 +178,8 @@ impl BentenContext {
             ImEvent::Done => {
                 if self.current_state==InputMethodState::Inactive {
                     // Focus lost, reset states
-                    self.engine.reset();
+                    self.engine.clear();
+                    self.label_timer.disarm().unwrap();
 
                     // Input deactivated, stop repeating
                     self.timer.disarm().unwrap();
@@ -132,35 +208,14 @@ impl BentenContext {
                 if self.current_state==InputMethodState::Active && self.mod_state {
                     match state {
                         KeyState::Pressed => {
-                            match self.engine.on_key_press((key + 8) as u16) {
-                                BentenResponse::Empty => {
-                                    self.im.set_preedit_string(String::new(), -1, -1);
-                                },
-
-                                BentenResponse::Function(f) => {
-                                    match f {
-                                        _ => self.im.set_preedit_string(String::new(), -1, -1),
-                                    }
-                                },
-
-                                BentenResponse::Undefined => {
-                                    self.vk.key(time, key, state as _);
-                                    self.im.set_preedit_string(String::new(), -1, -1);
-                                    return
-                                },
-
-                                BentenResponse::Commit(s) => { 
-                                    self.engine.reset();
-                                    self.im.commit_string(s);
-                                    self.im.set_preedit_string(String::new(), -1, -1);
-                                },
-
-                                BentenResponse::Suggest(s) => {
-                                    let len = s.len();
-                                    self.im.set_preedit_string(s, 0, len as _);
-                                }
+                            let response = self.engine.on_key_press((key + 8) as u16);
+                            let preedit = shows_preedit(&response);
+                            if !self.handle_response(response, time, key, state) {
+                                return
                             }
 
+                            self.show_method_change(preedit);
+
                             self.im.commit(self.serial);
                             self.serial += 1;
 
@@ -183,6 +238,11 @@ impl BentenContext {
                         KeyState::Released => {
                             // If user released the last pressed key, clear the timer and state
                             self.engine.on_key_release((key + 8) as u16);
+                            if self.show_method_change(false) {
+                                self.im.commit(self.serial);
+                                self.serial += 1;
+                            }
+
                             if let Some((.., ref mut press_state)) = self.repeat_state {
                                 if press_state.is_pressing(key) {
                                     self.timer.disarm().unwrap();
@@ -231,6 +291,99 @@ impl BentenContext {
         }
     }
 
+    /// Applies an engine response to the input method, returns false if the key was forwarded instead
+    fn handle_response(&mut self, response: BentenResponse, time: u32, key: u32, state: KeyState) -> bool {
+        match response {
+            BentenResponse::Empty => {
+                self.set_preedit(String::new(), -1, -1);
+            },
+
+            BentenResponse::Function(f) => {
+                match f {
+                    // key codes below 8 have no evdev code
+                    Function::EmitKey(key_code) => match key_code.checked_sub(8) {
+                        Some(key) => {
+                            self.vk.key(time, key as u32, KeyState::Pressed as _);
+                            self.vk.key(time, key as u32, KeyState::Released as _);
+                        },
+                        None => log::warn!("cannot emit key code {}", key_code),
+                    },
+                    _ => self.set_preedit(String::new(), -1, -1),
+                }
+            },
+
+            // a modifier pressed while something is being typed leaves it in the engine, and so in the preedit
+            BentenResponse::Undefined => {
+                self.vk.key(time, key, state as _);
+                if self.engine.is_empty() {
+                    self.set_preedit(String::new(), -1, -1);
+                }
+
+                return false
+            },
+
+            BentenResponse::Commit(s) => { 
+                self.engine.reset();
+                self.im.commit_string(s);
+                self.set_preedit(String::new(), -1, -1);
+            },
+
+            BentenResponse::Suggest(s) => {
+                let len = s.len();
+                self.set_preedit(s, 0, len as _);
+            },
+
+            BentenResponse::Hint(s, hint) => {
+                let len = s.len();
+                let preedit = if s.is_empty() { hint } else { format!("{} {}", s, hint) };
+                self.set_preedit(preedit, len as _, len as _);
+            },
+
+            BentenResponse::Replace(previous, s) => {
+                self.im.delete_surrounding_text(previous.len() as _, 0);
+                self.im.commit_string(s);
+                self.set_preedit(String::new(), -1, -1);
+            },
+
+            // The engine still holds state for the inner response, so it must not be reset here
+            BentenResponse::CommitThen(s, then) => {
+                self.im.commit_string(s);
+                self.set_preedit(String::new(), -1, -1);
+                self.im.commit(self.serial);
+                self.serial += 1;
+                return self.handle_response(*then, time, key, state);
+            }
+        }
+
+        true
+    }
+
+    /// Shows the label of the method switched to as preedit for `LABEL_DURATION`, unless there is text in it already,
+    /// returns whether it did
+    fn show_method_change(&mut self, preedit: bool) -> bool {
+        match self.publish_events() {
+            Some(label) if !preedit => {
+                let len = label.len();
+                self.update_preedit(label, len as _, len as _, true);
+                self.label_timer.set_timeout(&LABEL_DURATION).unwrap();
+                true
+            },
+            _ => false
+        }
+    }
+
+    /// Clears the label of the method switched to once shown long enough
+    pub fn handle_label_timer_ev(&mut self) -> std::io::Result<()> {
+        self.label_timer.read()?;
+        if self.label_shown && self.current_state==InputMethodState::Active {
+            self.set_preedit(String::new(), -1, -1);
+            self.im.commit(self.serial);
+            self.serial += 1;
+        }
+
+        Ok(())
+    }
+
     pub fn handle_timer_ev(&mut self) -> std::io::Result<()> {
         // Read timer, this MUST be called or timer will be broken
         let overrun_count = self.timer.read()?;
@@ -271,4 +424,13 @@ impl BentenContext {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Whether the response leaves text in the preedit
+fn shows_preedit(response: &BentenResponse) -> bool {
+    match response {
+        BentenResponse::Suggest(_) | BentenResponse::Hint(..) => true,
+        BentenResponse::CommitThen(_, then) => shows_preedit(then),
+        _ => false,
+    }
+}
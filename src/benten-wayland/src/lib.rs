@@ -1,7 +1,7 @@
 mod context;
 use context::BentenContext;
 
-use benten_ipc::{ Inotify, WatchRequest };
+use benten_ipc::{ Inotify, WatchRequest, StatusSocket };
 use std::path::PathBuf;
 use std::sync::mpsc::{ Receiver, sync_channel, channel };
 
@@ -41,6 +41,8 @@ const POLL_WAYLAND: Token = Token(0);
 const POLL_TIMER: Token = Token(1);
 const WAKE_TOKEN: Token = Token(2);
 const WATCHER_INOTIFY: Token = Token(3);
+const POLL_STATUS: Token = Token(4);
+const POLL_LABEL: Token = Token(5);
 
 impl State {
     pub fn new(ipc_path: PathBuf, layout: &str) -> Self {
@@ -92,6 +94,14 @@ impl State {
             Interest::READABLE
         ).expect("Register timer to the epoll()");
 
+        // Clears the label of the method switched to
+        let mut label_timer = TimerFd::new(ClockId::Monotonic).expect("Initialize label timer");
+        registry.register(
+            &mut label_timer,
+            POLL_LABEL,
+            Interest::READABLE
+        ).expect("Register label timer to the epoll()");
+
         // Initialise inotify watcher
         let (sender, requests): (_, Receiver<WatchRequest>) = sync_channel(16);
         let waker = Waker::new(poll.registry(), WAKE_TOKEN).unwrap();
@@ -102,8 +112,14 @@ impl State {
         sender.send(benten_ipc::WatchRequest { file: ipc_path.to_path_buf(), resp }).unwrap();
         waker.wake().unwrap();
 
+        // Status clients such as `benten-cli watch`
+        let status = StatusSocket::bind()
+            .and_then(|mut status| registry.register(&mut status, POLL_STATUS, Interest::READABLE).map(|_| status))
+            .map_err(|e| log::warn!("Running without the status socket: {}", e))
+            .ok();
+
         // Initialize context
-        let mut context = BentenContext::new(layout, vk, im, timer);
+        let mut context = BentenContext::new(layout, vk, im, timer, label_timer, status);
         event_queue.sync_roundtrip(&mut context, |_, _, _| ()).unwrap();
         log::info!("Server successfully initialised !");
 
@@ -153,13 +169,22 @@ impl State {
                         }
                     },
 
+                    POLL_LABEL => {
+                        if let Err(e) = self.context.handle_label_timer_ev() {
+                            break 'main Err(e);
+                        }
+                    },
+
                     WATCHER_INOTIFY => {
                         println!("Woken up by inotify token");
                         let new_layout = std::fs::read_to_string(&self.ipc_path).unwrap();
                         self.context.engine.set_layout(&new_layout);
+                        self.context.publish_events();
                         // self.watcher.handle_events();
                     },
 
+                    POLL_STATUS => self.context.accept_status_clients(),
+
                     WAKE_TOKEN => {
                         eprintln!("Woken up by wake token");
                     }
@@ -0,0 +1,4 @@
+pub mod table;
+pub mod xkb;
+pub use table::{ import_table, write_dict, TableFormat };
+pub use xkb::import_xkb;
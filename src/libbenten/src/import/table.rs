@@ -0,0 +1,163 @@
+use std::fs::read_to_string;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::BentenError;
+
+/// Table formats of other input method engines
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableFormat {
+    /// IBus table source, `code<TAB>phrase<TAB>frequency` lines between `BEGIN_TABLE` and `END_TABLE`
+    IBus,
+    /// Text export of fcitx tables (`mb2txt`, `libime_tabledict -d`), `code phrase` lines after `[Data]`
+    Fcitx,
+    /// RIME `.dict.yaml`, columns given by the header (`text<TAB>code<TAB>weight` by default)
+    Rime,
+}
+
+impl FromStr for TableFormat {
+    type Err = BentenError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ibus" => Ok(TableFormat::IBus),
+            "fcitx" => Ok(TableFormat::Fcitx),
+            "rime" => Ok(TableFormat::Rime),
+            _ => Err(BentenError::ImportError(format!("unknown table format `{}`, expected one of ibus, fcitx or rime", s))),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImportedEntry {
+    pub value: String,
+    pub sequence: String,
+    pub weight: Option<u32>,
+}
+
+/// Reads a table of another engine, keeping entries in the order of the file
+pub fn import_table(path: &Path, format: TableFormat) -> Result<Vec<ImportedEntry>, BentenError> {
+    let data = read_to_string(path)?;
+    Ok(match format {
+        TableFormat::IBus => parse_ibus(&data),
+        TableFormat::Fcitx => parse_fcitx(&data),
+        TableFormat::Rime => parse_rime(&data),
+    })
+}
+
+/// Writes entries as a `.dict` table
+pub fn write_dict(entries: &[ImportedEntry], path: &Path) -> Result<(), BentenError> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["value", "sequence", "weight"])?;
+    for entry in entries {
+        let weight = entry.weight.map(|weight| weight.to_string()).unwrap_or_default();
+        writer.write_record([&entry.value, &entry.sequence, &weight])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+fn parse_ibus(data: &str) -> Vec<ImportedEntry> {
+    data.lines()
+        .skip_while(|line| line.trim()!="BEGIN_TABLE")
+        .skip(1)
+        .take_while(|line| line.trim()!="END_TABLE")
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            let sequence = columns.next()?.trim();
+            let value = columns.next()?.trim();
+            let weight = columns.next().and_then(|weight| weight.trim().parse().ok());
+            entry(value, sequence, weight)
+        })
+        .collect()
+}
+
+fn parse_fcitx(data: &str) -> Vec<ImportedEntry> {
+    data.lines()
+        .skip_while(|line| !matches!(line.trim(), "[Data]" | "[数据]"))
+        .skip(1)
+        .take_while(|line| !line.starts_with('['))
+        .filter_map(|line| {
+            let mut columns = line.split_whitespace();
+            let sequence = columns.next()?;
+            let value = columns.next()?;
+            entry(value, sequence, None)
+        })
+        .collect()
+}
+
+fn parse_rime(data: &str) -> Vec<ImportedEntry> {
+    let mut lines = data.lines();
+    let mut columns = vec!["text", "code", "weight"];
+
+    // yaml header, only the order of the columns matters here
+    if data.trim_start().starts_with("---") {
+        let mut in_columns = false;
+        for line in lines.by_ref() {
+            let trimmed = line.trim();
+            if trimmed=="..." {
+                break;
+            }
+
+            if trimmed.starts_with("columns:") {
+                in_columns = true;
+                columns.clear();
+            } else if in_columns && trimmed.starts_with('-') {
+                columns.push(trimmed.trim_start_matches('-').trim());
+            } else if in_columns && !trimmed.is_empty() && !trimmed.starts_with('#') {
+                in_columns = false;
+            }
+        }
+    }
+
+    let column = |name: &str| columns.iter().position(|column| *column==name);
+    let (text, code, weight) = (column("text"), column("code"), column("weight"));
+
+    lines
+        .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let value = fields.get(text?)?.trim();
+            let sequence = fields.get(code?)?.trim();
+            let weight = weight.and_then(|i| fields.get(i)).and_then(|weight| weight.trim().parse().ok());
+            entry(value, sequence, weight)
+        })
+        .collect()
+}
+
+fn entry(value: &str, sequence: &str, weight: Option<u32>) -> Option<ImportedEntry> {
+    if value.is_empty() || sequence.is_empty() {
+        return None;
+    }
+
+    Some(ImportedEntry { value: value.to_string(), sequence: sequence.to_string(), weight })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(entries: &[(&str, &str, Option<u32>)]) -> Vec<ImportedEntry> {
+        entries.iter().map(|(value, sequence, weight)| entry(value, sequence, *weight).unwrap()).collect()
+    }
+
+    #[test]
+    fn ibus() {
+        let data = "SCHEME_TEST\nBEGIN_DEFINITION\nNAME = cangjie\nEND_DEFINITION\nBEGIN_TABLE\na\t日\t100\na\t曰\t90\nEND_TABLE\n";
+        assert_eq!(parse_ibus(data), entries(&[("日", "a", Some(100)), ("曰", "a", Some(90))]));
+    }
+
+    #[test]
+    fn fcitx() {
+        let data = "KeyCode=abcdefghijklmnopqrstuvwxyz\nLength=5\n[Data]\na 日\naa 昌\n[Phrase]\n日日\n";
+        assert_eq!(parse_fcitx(data), entries(&[("日", "a", None), ("昌", "aa", None)]));
+    }
+
+    #[test]
+    fn rime() {
+        let data = "---\nname: cangjie5\ncolumns:\n  - text\n  - code\n  - weight\n...\n\n# comment\n日\ta\t100\n曰\ta\n";
+        assert_eq!(parse_rime(data), entries(&[("日", "a", Some(100)), ("曰", "a", None)]));
+    }
+}
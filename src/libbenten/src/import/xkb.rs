@@ -0,0 +1,404 @@
+use std::collections::{ HashMap, HashSet };
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use crate::BentenError;
+use crate::methods::layout::{ Fallback, Layout, LayoutKind, Levels, Modifier, ModifierKind, keys };
+
+/// Keysyms of `keysymdef.h` which are characters, by name and by value
+static KEYSYMS: &str = include_str!("../../data/keysyms.txt");
+
+static KEYSYM_TABLE: OnceLock<Keysyms> = OnceLock::new();
+
+/// Included sections nested deeper than this are taken for an include cycle
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+struct Keysyms {
+    names: HashMap<&'static str, char>,
+    values: HashMap<u32, char>,
+}
+
+/// Keys of a layout being imported, by key code
+type Keys = HashMap<u16, Vec<Option<String>>>;
+
+/// Reads the `section` of an XKB symbols file, or its default section, into a layout of the
+/// first group with Shift on level 2, AltGr on level 3 and both on level 4. Sections it includes
+/// are read from the same directory first. Returns the layout along with the keys and keysyms
+/// which had to be skipped.
+pub fn import_xkb(path: &Path, section: Option<&str>, id: &str) -> Result<(Layout, Vec<String>), BentenError> {
+    let mut keys = HashMap::new();
+    let mut skipped = Vec::new();
+    read_symbols(path, section, &mut keys, &mut skipped, 0)?;
+    if keys.is_empty() {
+        return Err(BentenError::ImportError(format!("no key could be imported from `{}`", path.display())));
+    }
+
+    let level_count = keys.values().map(Vec::len).max().unwrap_or(1);
+    let mut modifiers = Vec::new();
+    let mut levels = HashMap::from([(1, HashSet::new())]);
+    if level_count>1 {
+        modifiers.push(Modifier { kind: ModifierKind::Set, key_codes: HashSet::from([50, 62]), transform: None });
+        levels.insert(2, HashSet::from([0]));
+    }
+
+    if level_count>2 {
+        modifiers.push(Modifier { kind: ModifierKind::Set, key_codes: HashSet::from([108]), transform: None });
+        levels.insert(3, HashSet::from([1]));
+        levels.insert(4, HashSet::from([0, 1]));
+    }
+
+    let layout = Layout {
+        id: id.to_string(),
+        kind: LayoutKind::Layout,
+        label: None,
+        modifiers,
+        levels: Levels::new(levels),
+        specs: None,
+        keys,
+        bindings: None,
+        compose: None,
+        table: None,
+        fallback: Fallback::PassThrough,
+        width: None,
+    };
+
+    Ok((layout, skipped))
+}
+
+/// Reads the keys of a section into `keys`, after those of the sections it includes
+fn read_symbols(path: &Path, section: Option<&str>, keys: &mut Keys, skipped: &mut Vec<String>, depth: usize) -> Result<(), BentenError> {
+    if depth>MAX_INCLUDE_DEPTH {
+        return Err(BentenError::ImportError(format!("xkb includes of `{}` nest too deeply", path.display())));
+    }
+
+    let data = strip_comments(&read_to_string(path)?);
+    let body = find_section(&data, section)
+        .ok_or_else(|| BentenError::ImportError(format!("xkb section `{}` not found in `{}`", section.unwrap_or("default"), path.display())))?;
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    for (include, augment) in includes(body) {
+        let (file, section) = match include.split_once('(') {
+            Some((file, section)) => (file, Some(section.trim_end_matches(')'))),
+            None => (include.as_str(), None),
+        };
+
+        let included = dir.join(file);
+        if !included.is_file() {
+            return Err(BentenError::ImportError(format!("xkb include `{}` not found in `{}`", include, dir.display())));
+        }
+
+        let mut included_keys = HashMap::new();
+        read_symbols(&included, section, &mut included_keys, skipped, depth+1)?;
+        for (key_code, values) in included_keys {
+            if !augment || !keys.contains_key(&key_code) {
+                merge(keys, key_code, values);
+            }
+        }
+    }
+
+    for (name, keysyms) in key_statements(body) {
+        let key_code = match keys::key_code(&name) {
+            Some(key_code) => key_code,
+            None => {
+                skipped.push(format!("unknown key <{}>", name));
+                continue;
+            }
+        };
+
+        let values: Vec<Option<String>> = keysyms.iter().take(4).map(|keysym| {
+            let c = keysym_to_char(keysym);
+            if c.is_none() && !matches!(keysym.as_str(), "NoSymbol" | "VoidSymbol") {
+                skipped.push(format!("keysym `{}` of <{}>, which is not a character", keysym, name));
+            }
+
+            c.map(String::from)
+        }).collect();
+
+        merge(keys, key_code, values);
+    }
+
+    Ok(())
+}
+
+/// Sets the values of a key, keeping those it had at the levels left without one, as for `NoSymbol`
+fn merge(keys: &mut Keys, key_code: u16, values: Vec<Option<String>>) {
+    let previous = keys.remove(&key_code).unwrap_or_default();
+    let mut values: Vec<Option<String>> = (0..values.len().max(previous.len()))
+        .map(|level| values.get(level).cloned().flatten().or_else(|| previous.get(level).cloned().flatten()))
+        .collect();
+
+    while let Some(None) = values.last() {
+        values.pop();
+    }
+
+    if !values.is_empty() {
+        keys.insert(key_code, values);
+    }
+}
+
+/// Sections included by `include`, `override` and `augment` statements, as `file` or `file(section)`,
+/// along with whether they only add keys which are not defined yet. The parts of a statement such as
+/// `"pc+us(basic)|inet(evdev)"` are split, `|` augmenting; parts for a group other than the first
+/// are left out.
+fn includes(body: &str) -> Vec<(String, bool)> {
+    let mut includes = Vec::new();
+    let mut offset = 0;
+    while let Some(open) = body[offset..].find('"').map(|i| offset+i+1) {
+        let close = match body[open..].find('"') {
+            Some(close) => open+close,
+            None => break,
+        };
+
+        offset = close+1;
+        let keyword = body[..open-1].trim_end().rsplit(|c: char| !c.is_alphanumeric()).next().unwrap_or_default();
+        let mut augment = match keyword {
+            "include" | "override" => false,
+            "augment" => true,
+            _ => continue,
+        };
+
+        let mut part = String::new();
+        for c in body[open..close].chars().chain(std::iter::once('+')) {
+            if c!='+' && c!='|' {
+                part.push(c);
+                continue;
+            }
+
+            match part.split_once(':') {
+                Some((name, "1")) => includes.push((name.to_string(), augment)),
+                Some(_) => {},
+                None if !part.is_empty() => includes.push((part.clone(), augment)),
+                None => {},
+            }
+
+            part.clear();
+            augment = c=='|';
+        }
+    }
+
+    includes
+}
+
+/// Converts a keysym name, `U<hex>`, `U+<hex>` or `0x<hex>` to its character
+fn keysym_to_char(keysym: &str) -> Option<char> {
+    let mut chars = keysym.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return Some(c);
+    }
+
+    if let Some(hex) = keysym.strip_prefix("U+").or_else(|| keysym.strip_prefix('U')) {
+        if let Ok(code_point) = u32::from_str_radix(hex, 16) {
+            return char::from_u32(code_point);
+        }
+    }
+
+    let table = KEYSYM_TABLE.get_or_init(Keysyms::parse);
+    if let Some(hex) = keysym.strip_prefix("0x") {
+        return match u32::from_str_radix(hex, 16).ok()? {
+            keysym @ 0x01000000..=0x0110FFFF => char::from_u32(keysym - 0x01000000),
+            keysym => table.values.get(&keysym).copied(),
+        };
+    }
+
+    table.names.get(keysym).copied()
+}
+
+impl Keysyms {
+    /// Parses lines of `<Name>;<Keysym>;<CodePoint>`
+    fn parse() -> Self {
+        let mut keysyms = Keysyms { names: HashMap::new(), values: HashMap::new() };
+        for line in KEYSYMS.lines().filter(|line| !line.starts_with('#')) {
+            let mut columns = line.split(';');
+            if let (Some(name), Some(Ok(value)), Some(Some(c))) = (
+                columns.next(),
+                columns.next().map(|value| u32::from_str_radix(value, 16)),
+                columns.next().map(|code_point| u32::from_str_radix(code_point, 16).ok().and_then(char::from_u32)),
+            ) {
+                keysyms.names.insert(name, c);
+                keysyms.values.entry(value).or_insert(c);
+            }
+        }
+
+        keysyms
+    }
+}
+
+fn strip_comments(data: &str) -> String {
+    data.lines()
+        .map(|line| line.find("//").map_or(line, |i| &line[..i]))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Body of `xkb_symbols "<section>" { ... }`, or of the section marked `default`, else the first one
+fn find_section<'a>(data: &'a str, section: Option<&str>) -> Option<&'a str> {
+    let mut first = None;
+    let mut offset = 0;
+    while let Some(start) = data[offset..].find("xkb_symbols").map(|i| offset+i) {
+        let line_start = data[..start].rfind('\n').map_or(0, |i| i+1);
+        let open = start + data[start..].find('{')?;
+        let name = data[start..open].split('"').nth(1).unwrap_or_default();
+        let is_default = data[line_start..start].contains("default");
+
+        let end = open + 1 + matching_brace(&data[open+1..])?;
+        let body = &data[open+1..end];
+
+        match section {
+            Some(section) if section==name => return Some(body),
+            None if is_default => return Some(body),
+            _ => {}
+        }
+
+        first.get_or_insert(body);
+        offset = end;
+    }
+
+    if section.is_none() { first } else { None }
+}
+
+/// Offset of the brace closing one that was just opened
+fn matching_brace(data: &str) -> Option<usize> {
+    let mut depth = 1;
+    for (i, c) in data.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth==0 {
+                    return Some(i);
+                }
+            },
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Every `key <NAME> { ... };` statement along with the keysyms of its first group
+fn key_statements(body: &str) -> Vec<(String, Vec<String>)> {
+    let mut statements = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = body[offset..].find("key <").map(|i| offset+i+5) {
+        let (name_end, open) = match (body[start..].find('>'), body[start..].find('{')) {
+            (Some(name_end), Some(open)) => (start+name_end, start+open+1),
+            _ => break,
+        };
+
+        let close = match matching_brace(&body[open..]) {
+            Some(close) => open+close,
+            None => break,
+        };
+
+        if let Some(keysyms) = first_group(&body[open..close]) {
+            statements.push((body[start..name_end].trim().to_string(), keysyms));
+        }
+
+        offset = close;
+    }
+
+    statements
+}
+
+/// Keysyms of a key's first group, written either bare as `[ a, A ]` or as `symbols[Group1] = [ a, A ]`,
+/// skipping the `type` and `actions` of the key.
+fn first_group(key: &str) -> Option<Vec<String>> {
+    let mut offset = 0;
+    while let Some(open) = key[offset..].find('[').map(|i| offset+i) {
+        let close = open + key[open..].find(']')?;
+        let content = key[open+1..close].trim();
+        offset = close+1;
+
+        // index of a statement such as `symbols[Group1]`
+        if content.starts_with("Group") {
+            continue;
+        }
+
+        let statement = key[..open].rsplit([',', '{']).next().unwrap_or_default().trim();
+        if statement.is_empty() || statement.starts_with("symbols[Group1]") {
+            return Some(content.split(',').map(|keysym| keysym.trim().to_string()).collect());
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keysyms() {
+        assert_eq!(keysym_to_char("a"), Some('a'));
+        assert_eq!(keysym_to_char("exclam"), Some('!'));
+        assert_eq!(keysym_to_char("Aacute"), Some('Á'));
+        assert_eq!(keysym_to_char("kana_A"), Some('ア'));
+        assert_eq!(keysym_to_char("Cyrillic_ya"), Some('я'));
+        assert_eq!(keysym_to_char("Greek_OMEGA"), Some('Ω'));
+        assert_eq!(keysym_to_char("U3042"), Some('あ'));
+        assert_eq!(keysym_to_char("U+3042"), Some('あ'));
+        assert_eq!(keysym_to_char("0x1003042"), Some('あ'));
+        assert_eq!(keysym_to_char("0x4b1"), Some('ア'));
+        assert_eq!(keysym_to_char("0xe9"), Some('é'));
+        assert_eq!(keysym_to_char("Ukrainian_ie"), Some('є'));
+        assert_eq!(keysym_to_char("BackSpace"), None);
+    }
+
+    #[test]
+    fn include_statements() {
+        let body = r#"
+            include "latin(type4)"
+            include "pc+us(basic):1|inet(evdev)+level3(ralt_switch):2"
+            augment "nbsp(level3)";
+            name[Group1]= "Test";
+        "#;
+
+        assert_eq!(includes(body), vec![
+            ("latin(type4)".to_string(), false),
+            ("pc".to_string(), false),
+            ("us(basic)".to_string(), false),
+            ("inet(evdev)".to_string(), true),
+            ("nbsp(level3)".to_string(), true),
+        ]);
+    }
+
+    #[test]
+    fn included_sections() {
+        let dir = std::env::temp_dir().join("benten-xkb");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("base"), "default xkb_symbols \"basic\" { key <AD01> { [ q, Q ] }; key <AD02> { [ w, W ] }; };").unwrap();
+        std::fs::write(dir.join("test"), "xkb_symbols \"kana\" { include \"base(basic)\" key <AD01> { [ kana_TA ] }; key <AD02> { [ NoSymbol, NoSymbol, Greek_omega ] }; };\nxkb_symbols \"broken\" { include \"missing\" };\nxkb_symbols \"empty\" { key <AD01> { [ BackSpace ] }; };").unwrap();
+
+        let (layout, skipped) = import_xkb(&dir.join("test"), Some("kana"), "test").unwrap();
+        assert_eq!(layout.keys.get(&24), Some(&vec![Some("タ".to_string()), Some("Q".to_string())]));
+        assert_eq!(layout.keys.get(&25), Some(&vec![Some("w".to_string()), Some("W".to_string()), Some("ω".to_string())]));
+        assert!(skipped.is_empty());
+
+        assert!(import_xkb(&dir.join("test"), Some("broken"), "test").is_err());
+        assert!(import_xkb(&dir.join("test"), Some("empty"), "test").is_err());
+    }
+
+    #[test]
+    fn key_groups() {
+        let body = r#"
+            name[Group1]= "Test";
+            key <AD01> { [ q, Q, at ] };
+            key <AC01> { type[Group1] = "FOUR_LEVEL", symbols[Group1] = [ a, A, aacute, Aacute ] };
+            key <RALT> { type[Group1] = "ONE_LEVEL", actions[Group1] = [ SetMods(modifiers=Mod5) ] };
+        "#;
+
+        let statements = key_statements(body);
+        assert_eq!(statements.len(), 2);
+        assert_eq!(statements[0], ("AD01".to_string(), vec!["q".to_string(), "Q".to_string(), "at".to_string()]));
+        assert_eq!(statements[1].1.len(), 4);
+    }
+
+    #[test]
+    fn sections() {
+        let data = "xkb_symbols \"basic\" { key <AD01> { [ q ] }; };\ndefault partial xkb_symbols \"other\" { key <AD01> { [ w ] }; };";
+        assert!(find_section(data, Some("basic")).unwrap().contains("[ q ]"));
+        assert!(find_section(data, None).unwrap().contains("[ w ]"));
+        assert!(find_section(data, Some("missing")).is_none());
+    }
+}
@@ -1,12 +1,28 @@
 mod methods;
+mod variants;
+mod transform;
+pub mod import;
 
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
 use std::path::PathBuf;
-use methods::State;
+use methods::{ State, MAX_METHOD_STACK };
+use variants::{ Variants, Variant, is_variation_selector };
 use thiserror::Error;
 
+pub use methods::layout::Layout;
+pub use methods::table::Table;
+pub use methods::table::history::History;
+pub use transform::{ Transform, Width };
+
 pub struct BentenEngine {
     state: State,
+    preloaded: HashMap<String, State>, //layouts of `cfg.preload` other than the current one
     cfg: BentenConfig,
+    variants: Variants,
+    variant: Option<Variant>,
+    events: Vec<BentenEvent>, //not yet taken by the frontend, see `BentenEngine::events`
 }
 
 impl BentenEngine {
@@ -16,18 +32,82 @@ impl BentenEngine {
         let state = State::new(&cfg.id, &cfg.dir).unwrap();
             // .map_err(|_| panic!("layout `{}` not found", &cfg.id)).unwrap();
 
-        BentenEngine { state, cfg }
+        let variants = Variants::load(&cfg.dir);
+
+        let mut engine = BentenEngine { state, preloaded: HashMap::new(), cfg, variants, variant: None, events: Vec::new() };
+        engine.preload();
+        engine
     }
 
     pub fn on_key_press(&mut self, key_code: u16) -> BentenResponse {
-        let rep = self.state.methods.get_mut(&self.state.current_method).unwrap().on_key_press(key_code);
-        if let BentenResponse::Function(ref function) = rep {
-            if let Some(response) = self.exec_function(function) {
-                return response;
-            }
+        let method = self.state.current_method.clone();
+        let current = self.state.methods.get_mut(&method).unwrap();
+        let functions = current.bindings(key_code);
+        let was_empty = current.is_empty();
+        let rep = current.on_key_press(key_code);
+
+        // the variant to cycle is the one of the text typed before the key
+        let mut rep = if functions.iter().any(Function::cycles_variant) {
+            rep
+        } else {
+            self.track_variant(rep)
+        };
+
+        // bound keys are never passed on, even when the method has nothing for them
+        for function in &functions {
+            let (layout, current) = (self.state.id.clone(), self.state.current_method.clone());
+            rep = match self.exec(function, was_empty) {
+                Some(next) => then(rep, next),
+                None if self.state.id!=layout || self.state.current_method!=current => switched(rep, function),
+                None => bound(rep, function),
+            };
+        }
+
+        // only a commit of the one-shot method itself, not one of the method the key switched from
+        if self.state.one_shot.as_ref().is_some_and(|m| *m==method && *m==self.state.current_method)
+            && matches!(rep, BentenResponse::Commit(_) | BentenResponse::CommitThen(..)) {
+            self.return_to_previous_method();
+        }
+
+        let rep = self.hint_reverse_lookup(&method, rep);
+        match self.width(&method) {
+            Some(width) => with_width(rep, width),
+            None => rep,
+        }
+    }
+
+    /// Width the output of `method` is converted to
+    fn width(&self, method: &str) -> Option<Width> {
+        match self.state.widths.get(method) {
+            Some(width) => *width,
+            None => self.state.methods.get(method).and_then(|method| method.width()),
+        }
+    }
+
+    /// Follows a commit with the key sequences of the committed text in the `reverse_lookup`
+    /// method, unless it was committed with that method
+    fn hint_reverse_lookup(&self, method: &str, rep: BentenResponse) -> BentenResponse {
+        let value = match &rep {
+            BentenResponse::Commit(value) => value,
+            _ => return rep,
+        };
+
+        match &self.state.reverse_lookup {
+            Some(reverse_lookup) if reverse_lookup!=method => {
+                let sequences = self.reverse_lookup(value.trim_end_matches(is_variation_selector), reverse_lookup);
+                if sequences.is_empty() {
+                    return rep;
+                }
+
+                BentenResponse::CommitThen(value.to_string(), Box::new(BentenResponse::Hint(String::new(), sequences.join(" "))))
+            },
+            _ => rep
         }
+    }
 
-        return rep;
+    /// Key sequences producing `value` in the given method of the current layout
+    pub fn reverse_lookup(&self, value: &str, method: &str) -> Vec<String> {
+        self.state.methods.get(method).map_or(Vec::new(), |method| method.reverse_lookup(value))
     }
 
     pub fn on_key_release(&mut self, key_code: u16) -> BentenResponse {
@@ -36,40 +116,364 @@ impl BentenEngine {
             self.exec_function(function);
         }
 
-        return rep;
+        rep
     }
 
+    /// Candidates of the current method as pairs of value and key sequence
+    pub fn candidates(&self) -> Vec<(String, String)> {
+        self.state.methods.get(&self.state.current_method).map_or(Vec::new(), |method| method.candidates())
+    }
+
+    /// Whether the current method holds nothing being typed, see `GenericMethodTrait::is_empty`
+    pub fn is_empty(&self) -> bool {
+        self.state.methods.get(&self.state.current_method).is_none_or(|method| method.is_empty())
+    }
+
+    /// Id of the current layout
+    pub fn layout(&self) -> &str {
+        &self.state.id
+    }
+
+    /// Id of the current method
+    pub fn current_method(&self) -> &str {
+        &self.state.current_method
+    }
+
+    /// Label of the current method, its id when it has none
+    pub fn label(&self) -> &str {
+        self.state.methods.get(&self.state.current_method).map_or(&self.state.current_method, |method| method.label())
+    }
+
+    /// Takes what happened in the engine since the last call, for frontends to show
+    pub fn events(&mut self) -> Vec<BentenEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn method_changed(&mut self) {
+        self.events.push(BentenEvent::MethodChanged {
+            layout: self.state.id.to_string(),
+            method: self.state.current_method.to_string(),
+            label: self.label().to_string(),
+        });
+    }
+
+    /// Loads the layout from its file, along with the preloaded ones
     pub fn set_layout(&mut self, name: &str) {
         self.state = State::new(name, &self.cfg.dir).unwrap();
+        self.variant = None;
+        self.preload();
+        self.method_changed();
+    }
+
+    fn preload(&mut self) {
+        self.preloaded.clear();
+        for id in &self.cfg.preload {
+            if *id==self.state.id {
+                continue;
+            }
+
+            match State::new(id, &self.cfg.dir) {
+                Ok(state) => { self.preloaded.insert(id.to_string(), state); },
+                Err(e) => log::warn!("could not preload layout `{}`: {}", id, e),
+            }
+        }
+    }
+
+    /// Switches to layout `id`, without reading it again when it is preloaded
+    pub fn change_layout(&mut self, id: &str) {
+        if id==self.state.id {
+            return;
+        }
+
+        let state = match self.preloaded.remove(id) {
+            Some(state) => state,
+            None => match State::new(id, &self.cfg.dir) {
+                Ok(state) => state,
+                Err(e) => {
+                    log::warn!("could not load layout `{}`: {}", id, e);
+                    return;
+                }
+            }
+        };
+
+        let mut previous = std::mem::replace(&mut self.state, state);
+        for method in previous.methods.values_mut() {
+            method.clear();
+        }
+
+        self.variant = None;
+        if self.cfg.preload.contains(&previous.id) {
+            self.preloaded.insert(previous.id.to_string(), previous);
+        }
+
+        self.method_changed();
+    }
+
+    /// Layout following the current one in `cfg.preload`, wrapping around
+    fn next_layout(&self) -> Option<String> {
+        let next = match self.cfg.preload.iter().position(|id| *id==self.state.id) {
+            Some(position) => (position+1) % self.cfg.preload.len(),
+            None => 0,
+        };
+
+        self.cfg.preload.get(next).cloned()
     }
 
+    /// Runs a bound function, returning what the frontend should do if anything
     pub fn exec_function(&mut self, function: &Function) -> Option<BentenResponse> {
+        self.exec(function, self.is_empty())
+    }
+
+    /// Runs `function`, the `IfEmpty` ones checking `was_empty`, whether the current method held
+    /// nothing before the key it is bound to
+    fn exec(&mut self, function: &Function, was_empty: bool) -> Option<BentenResponse> {
         match function {
-            Function::ChangeMethodTo(m) => self.state.current_method = m.to_string(),
+            Function::ChangeMethodTo(m) => self.change_method(m),
             Function::CommitThenChangeMethodTo(v, m) => {
-                self.state.current_method = m.to_string();
-                return Some(BentenResponse::Commit(v.to_string()));
-            },  
-            _ => {}
-        }  
+                self.change_method(m);
+                return Some(BentenResponse::Commit(self.commit_variant(v.to_string())));
+            },
+            Function::IfEmptyChangeMethodTo(m) => {
+                if was_empty {
+                    self.change_method(m);
+                }
+            },
+            Function::CycleVariant => {
+                let variant = self.variant.as_mut()?;
+                let previous = variant.selector();
+                variant.cycle(&self.variants);
+
+                if variant.committed {
+                    return Some(BentenResponse::Replace(previous, variant.selector()));
+                }
+
+                return Some(BentenResponse::Suggest(format!("{}{}", variant.text, variant.selector())));
+            },
+            Function::NextLayout => {
+                let id = self.next_layout()?;
+                self.change_layout(&id);
+            },
+            Function::ChangeLayoutTo(id) => self.change_layout(id),
+            Function::Commit(text) => return Some(BentenResponse::Commit(self.commit_variant(text.to_string()))),
+            Function::EmitKey(_) => return Some(BentenResponse::Function(function.clone())),
+            Function::Reset => {
+                self.clear();
+                return Some(BentenResponse::Empty);
+            },
+            Function::ToggleMethod(a, b) => {
+                let m = if self.state.current_method==*a { b } else { a };
+                self.change_method(&m.to_string());
+            },
+            Function::PreviousMethod => {
+                if let Some(m) = self.state.method_stack.last().cloned() {
+                    self.change_method(&m);
+                }
+            },
+            Function::ReturnToPreviousMethod => self.return_to_previous_method(),
+            Function::ToggleWidth(width) => {
+                let method = self.state.current_method.clone();
+                let toggled = if self.width(&method)==Some(*width) { None } else { Some(*width) };
+                self.state.widths.insert(method, toggled);
+            },
+            // already in the method, there is nothing to return to after the commit
+            Function::OneShot(m) if self.state.current_method==*m => {},
+            Function::OneShot(m) => {
+                self.change_method(m);
+                if self.state.current_method==*m {
+                    self.state.one_shot = Some(m.to_string());
+                }
+            },
+            Function::IfEmptyReturnToPreviousMethod => {
+                if was_empty {
+                    self.return_to_previous_method();
+                }
+            },
+            Function::ClearPreedit => {
+                if let Some(method) = self.state.methods.get_mut(&self.state.current_method) {
+                    method.reset();
+                }
+
+                self.variant = None;
+                return Some(BentenResponse::Empty);
+            },
+            Function::Sequence(functions) => {
+                let mut rep: Option<BentenResponse> = None;
+                for function in functions {
+                    if let Some(next) = self.exec(function, was_empty) {
+                        rep = Some(match rep {
+                            Some(rep) => then(rep, next),
+                            None => next,
+                        });
+                    }
+                }
+
+                return rep;
+            },
+        }
 
         None
     }
 
+    /// Switches to method `m` of the current layout, dropping whatever was being typed in the previous one
+    fn change_method(&mut self, m: &str) {
+        if m==self.state.current_method {
+            return;
+        }
+
+        if !self.state.methods.contains_key(m) {
+            log::warn!("layout `{}` has no method `{}`", self.state.id, m);
+            return;
+        }
+
+        if let Some(method) = self.state.methods.get_mut(&self.state.current_method) {
+            method.clear();
+        }
+
+        self.state.one_shot = None;
+        let previous = std::mem::replace(&mut self.state.current_method, m.to_string());
+        self.state.method_stack.push(previous);
+        if self.state.method_stack.len()>MAX_METHOD_STACK {
+            self.state.method_stack.remove(0);
+        }
+
+        self.method_changed();
+    }
+
+    /// Switches back to the method the current one was switched to from, forgetting the switch
+    fn return_to_previous_method(&mut self) {
+        while let Some(m) = self.state.method_stack.pop() {
+            if m==self.state.current_method || !self.state.methods.contains_key(&m) {
+                continue;
+            }
+
+            if let Some(method) = self.state.methods.get_mut(&self.state.current_method) {
+                method.clear();
+            }
+
+            self.state.one_shot = None;
+            self.state.current_method = m;
+            self.method_changed();
+            return;
+        }
+    }
+
+    /// Remembers the text a variation selector can be chosen for, carrying a selector chosen while
+    /// the text was suggested over to its commit
+    fn track_variant(&mut self, rep: BentenResponse) -> BentenResponse {
+        match rep {
+            BentenResponse::Commit(value) => BentenResponse::Commit(self.commit_variant(value)),
+            BentenResponse::CommitThen(value, then) => {
+                let value = self.commit_variant(value);
+                BentenResponse::CommitThen(value, Box::new(self.track_variant(*then)))
+            },
+
+            BentenResponse::Suggest(value) => {
+                self.variant = Some(Variant::new(value.clone(), false));
+                BentenResponse::Suggest(value)
+            },
+
+            BentenResponse::Hint(value, hint) => {
+                self.variant = Some(Variant::new(value.clone(), false));
+                BentenResponse::Hint(value, hint)
+            },
+
+            BentenResponse::Function(_) => rep,
+            _ => {
+                self.variant = None;
+                rep
+            }
+        }
+    }
+
+    fn commit_variant(&mut self, value: String) -> String {
+        let mut variant = Variant::new(value, true);
+        if let Some(previous) = self.variant.take() {
+            if !previous.committed && previous.text==variant.text {
+                variant.selector = previous.selector;
+            }
+        }
+
+        let value = format!("{}{}", variant.text, variant.selector());
+        self.variant = Some(variant);
+        value
+    }
+
+    /// Drops whatever is being typed, as frontends do on commit, modifiers locked or latched staying
+    /// so. The committed text can still have its variant cycled until the next key.
     pub fn reset(&mut self) {
+        self.variant = self.variant.take().filter(|variant| variant.committed);
         for method in self.state.methods.values_mut() {
             method.reset();
         }
     }
+
+    /// Drops whatever is being typed and releases every modifier, as frontends do when losing focus
+    pub fn clear(&mut self) {
+        self.variant = None;
+        for method in self.state.methods.values_mut() {
+            method.clear();
+        }
+    }
+}
+
+/// Follows `rep` with `next`, keeping whatever `rep` commits. Any other response of `rep` is
+/// replaced by `next`, which `check_sequence` makes sure does not drop one left to the frontend.
+fn then(rep: BentenResponse, next: BentenResponse) -> BentenResponse {
+    match (rep, next) {
+        (BentenResponse::Commit(value), BentenResponse::Empty) => BentenResponse::Commit(value),
+        (BentenResponse::Commit(value), next) => BentenResponse::CommitThen(value, Box::new(next)),
+        (BentenResponse::CommitThen(value, rep), next) => BentenResponse::CommitThen(value, Box::new(then(*rep, next))),
+        (_, next) => next,
+    }
+}
+
+/// Keeps `rep`, the key being taken by `function` rather than passed on when `rep` has nothing for it
+fn bound(rep: BentenResponse, function: &Function) -> BentenResponse {
+    match rep {
+        BentenResponse::Undefined => BentenResponse::Function(function.clone()),
+        BentenResponse::CommitThen(value, rep) => BentenResponse::CommitThen(value, Box::new(bound(*rep, function))),
+        rep => rep,
+    }
+}
+
+/// Keeps what `rep` commits, anything else it shows being dropped along with the method `function`
+/// switched away from
+fn switched(rep: BentenResponse, function: &Function) -> BentenResponse {
+    match rep {
+        BentenResponse::Commit(_) | BentenResponse::Replace(..) => rep,
+        BentenResponse::CommitThen(value, rep) => BentenResponse::CommitThen(value, Box::new(switched(*rep, function))),
+        _ => BentenResponse::Function(function.clone()),
+    }
+}
+
+/// Converts the text `rep` commits or suggests to `width`, leaving hints as they are
+fn with_width(rep: BentenResponse, width: Width) -> BentenResponse {
+    match rep {
+        BentenResponse::Commit(value) => BentenResponse::Commit(width.apply(&value)),
+        BentenResponse::Suggest(value) => BentenResponse::Suggest(width.apply(&value)),
+        BentenResponse::Hint(value, hint) => BentenResponse::Hint(width.apply(&value), hint),
+        BentenResponse::CommitThen(value, rep) => BentenResponse::CommitThen(width.apply(&value), Box::new(with_width(*rep, width))),
+        rep => rep,
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum BentenResponse {
     Commit(String),
     Suggest(String),
+    Hint(String, String), //Suggest the first string, showing the second after it without being part of the text
     Undefined, //KeyCode is not defined
     Empty, //KeyCode found but didnt have anything to return, intentional (like special keys eg. Han key)
-    Function(Function)
+    Function(Function),
+    CommitThen(String, Box<BentenResponse>), //Commit the string first, then handle the inner response
+    Replace(String, String), //Replace the first string, found right before the cursor, by the second
+}
+
+/// Changes frontends may show, such as in a status bar
+#[derive(Debug, Clone, PartialEq)]
+pub enum BentenEvent {
+    /// The current method changed, along with the layout or not
+    MethodChanged { layout: String, method: String, label: String },
 }
 
 #[derive(Error, Debug)]
@@ -82,27 +486,134 @@ pub enum BentenError {
     ZmeraldError(#[from] zmerald::de::Error),
     #[error("kb parse error")]
     KbParseError,
+    #[error("import error: {0}")]
+    ImportError(String),
 }
 
 pub struct BentenConfig {
     pub id: String,
-    pub dir: PathBuf
+    pub dir: PathBuf,
+    pub preload: Vec<String>, //layouts kept loaded to switch to them instantly
 }
 
 impl Default for BentenConfig {
     fn default() -> Self {
         BentenConfig {
             dir: xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home(),
-            id: "layout id was not defined".to_string()
+            id: "layout id was not defined".to_string(),
+            preload: Vec::new(),
         }
     }
 }
 
+impl BentenConfig {
+    /// Config starting with layout `id`, the rest being read from `benten.zm` when there is one
+    pub fn load(id: &str) -> Self {
+        let mut cfg = BentenConfig { id: id.to_string(), ..BentenConfig::default() };
+        let path = cfg.dir.join("benten.zm");
+        if let Ok(file) = File::open(&path) {
+            match zmerald::from_reader::<_, ConfigFile>(BufReader::new(file)) {
+                Ok(file) => cfg.preload = file.preload,
+                Err(e) => log::warn!("could not read `{}`: {}", path.display(), e),
+            }
+        }
+
+        cfg
+    }
+}
+
+/// Points the cache and data directories, where tables are compiled and learned, to temporary ones
+/// rather than those of the user running the tests
+#[cfg(test)]
+pub(crate) fn test_dirs() {
+    static DIRS: std::sync::Once = std::sync::Once::new();
+    DIRS.call_once(|| {
+        let dir = std::env::temp_dir().join("benten-tests");
+        std::env::set_var("XDG_CACHE_HOME", dir.join("cache"));
+        std::env::set_var("XDG_DATA_HOME", dir.join("data"));
+    });
+}
+
+/// `$XDG_CONFIG_HOME/benten/benten.zm`
+#[derive(Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    preload: Vec<String>,
+}
+
 
 use serde::Deserialize;
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub enum Function {
     ChangeMethodTo(String),
     CommitThenChangeMethodTo(String, String),
     IfEmptyChangeMethodTo(String),
+    CycleVariant,
+    NextLayout,
+    ChangeLayoutTo(String),
+    Commit(String),
+    EmitKey(#[serde(deserialize_with = "methods::layout::keys::deserialize_key")] u16), //left to the frontend, which sends a press and release of the key
+    Reset,
+    ToggleMethod(String, String),
+    PreviousMethod, //switches to the method switched away from last, so that using it again switches back
+    ReturnToPreviousMethod, //switches back to whichever method switched to the current one, see `State::method_stack`
+    IfEmptyReturnToPreviousMethod,
+    OneShot(String), //switches to the method until its next commit, then returns to the current one
+    ToggleWidth(Width), //converts what the current method commits and suggests to the width, or stops converting it
+    ClearPreedit,
+    Sequence(#[serde(deserialize_with = "deserialize_sequence")] Vec<Function>),
+}
+
+impl Function {
+    /// Whether the function may respond, replacing the response of the functions run before it
+    fn responds(&self) -> bool {
+        match self {
+            Function::CommitThenChangeMethodTo(..) | Function::CycleVariant | Function::Commit(_)
+                | Function::EmitKey(_) | Function::Reset | Function::ClearPreedit => true,
+            Function::Sequence(functions) => functions.iter().any(Function::responds),
+            _ => false,
+        }
+    }
+
+    /// Whether the function cycles the variant of the text typed before the key it is bound to
+    fn cycles_variant(&self) -> bool {
+        match self {
+            Function::CycleVariant => true,
+            Function::Sequence(functions) => functions.iter().any(Function::cycles_variant),
+            _ => false,
+        }
+    }
+
+    /// Whether the response of the function is left to the frontend rather than shown as preedit
+    fn is_frontend_action(&self) -> bool {
+        match self {
+            Function::EmitKey(_) | Function::CycleVariant => true,
+            Function::Sequence(functions) => functions.iter().any(Function::is_frontend_action),
+            _ => false,
+        }
+    }
+}
+
+/// Rejects functions run one after the other, as in a `Sequence` or the bindings of a key, where
+/// one left to the frontend is followed by another which responds, as `then` would drop it
+pub(crate) fn check_sequence<'a>(functions: impl IntoIterator<Item = &'a Function>) -> Result<(), String> {
+    let mut action: Option<&Function> = None;
+    for function in functions {
+        if let Some(action) = action.filter(|_| function.responds()) {
+            return Err(format!("`{:?}` cannot be followed by `{:?}`, which would replace its response", action, function));
+        }
+
+        if function.is_frontend_action() {
+            action = Some(function);
+        }
+    }
+
+    Ok(())
+}
+
+fn deserialize_sequence<'de, D>(deserializer: D) -> Result<Vec<Function>, D::Error>
+where D: serde::Deserializer<'de> {
+    let functions = Vec::<Function>::deserialize(deserializer)?;
+    check_sequence(&functions).map_err(serde::de::Error::custom)?;
+    Ok(functions)
 }
\ No newline at end of file
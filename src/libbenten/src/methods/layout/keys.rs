@@ -0,0 +1,216 @@
+use serde::{ Deserialize, Deserializer };
+use serde::de::{ self, Visitor };
+use std::collections::{ HashMap, HashSet };
+use std::fmt;
+
+/// Internal key codes (evdev code + 8, as used by XKB) along with the names they can be referred
+/// to by in layouts: the XKB key name, the evdev `KEY_` name and a keysym-like name.
+const KEYS: &[(u16, &[&str])] = &[
+    (9,   &["ESC", "KEY_ESC", "Escape"]),
+    (10,  &["AE01", "KEY_1"]),
+    (11,  &["AE02", "KEY_2"]),
+    (12,  &["AE03", "KEY_3"]),
+    (13,  &["AE04", "KEY_4"]),
+    (14,  &["AE05", "KEY_5"]),
+    (15,  &["AE06", "KEY_6"]),
+    (16,  &["AE07", "KEY_7"]),
+    (17,  &["AE08", "KEY_8"]),
+    (18,  &["AE09", "KEY_9"]),
+    (19,  &["AE10", "KEY_0"]),
+    (20,  &["AE11", "KEY_MINUS", "Minus"]),
+    (21,  &["AE12", "KEY_EQUAL", "Equal"]),
+    (22,  &["BKSP", "KEY_BACKSPACE", "BackSpace"]),
+    (23,  &["TAB", "KEY_TAB", "Tab"]),
+    (24,  &["AD01", "KEY_Q"]),
+    (25,  &["AD02", "KEY_W"]),
+    (26,  &["AD03", "KEY_E"]),
+    (27,  &["AD04", "KEY_R"]),
+    (28,  &["AD05", "KEY_T"]),
+    (29,  &["AD06", "KEY_Y"]),
+    (30,  &["AD07", "KEY_U"]),
+    (31,  &["AD08", "KEY_I"]),
+    (32,  &["AD09", "KEY_O"]),
+    (33,  &["AD10", "KEY_P"]),
+    (34,  &["AD11", "KEY_LEFTBRACE", "BracketLeft"]),
+    (35,  &["AD12", "KEY_RIGHTBRACE", "BracketRight"]),
+    (36,  &["RTRN", "KEY_ENTER", "Return"]),
+    (37,  &["LCTL", "KEY_LEFTCTRL", "Control_L"]),
+    (38,  &["AC01", "KEY_A"]),
+    (39,  &["AC02", "KEY_S"]),
+    (40,  &["AC03", "KEY_D"]),
+    (41,  &["AC04", "KEY_F"]),
+    (42,  &["AC05", "KEY_G"]),
+    (43,  &["AC06", "KEY_H"]),
+    (44,  &["AC07", "KEY_J"]),
+    (45,  &["AC08", "KEY_K"]),
+    (46,  &["AC09", "KEY_L"]),
+    (47,  &["AC10", "KEY_SEMICOLON", "Semicolon"]),
+    (48,  &["AC11", "KEY_APOSTROPHE", "Apostrophe"]),
+    (49,  &["TLDE", "KEY_GRAVE", "Grave"]),
+    (50,  &["LFSH", "KEY_LEFTSHIFT", "Shift_L"]),
+    (51,  &["BKSL", "KEY_BACKSLASH", "Backslash"]),
+    (52,  &["AB01", "KEY_Z"]),
+    (53,  &["AB02", "KEY_X"]),
+    (54,  &["AB03", "KEY_C"]),
+    (55,  &["AB04", "KEY_V"]),
+    (56,  &["AB05", "KEY_B"]),
+    (57,  &["AB06", "KEY_N"]),
+    (58,  &["AB07", "KEY_M"]),
+    (59,  &["AB08", "KEY_COMMA", "Comma"]),
+    (60,  &["AB09", "KEY_DOT", "Period"]),
+    (61,  &["AB10", "KEY_SLASH", "Slash"]),
+    (62,  &["RTSH", "KEY_RIGHTSHIFT", "Shift_R"]),
+    (64,  &["LALT", "KEY_LEFTALT", "Alt_L"]),
+    (65,  &["SPCE", "KEY_SPACE", "Space"]),
+    (66,  &["CAPS", "KEY_CAPSLOCK", "Caps_Lock"]),
+    (67,  &["FK01", "KEY_F1", "F1"]),
+    (68,  &["FK02", "KEY_F2", "F2"]),
+    (69,  &["FK03", "KEY_F3", "F3"]),
+    (70,  &["FK04", "KEY_F4", "F4"]),
+    (71,  &["FK05", "KEY_F5", "F5"]),
+    (72,  &["FK06", "KEY_F6", "F6"]),
+    (73,  &["FK07", "KEY_F7", "F7"]),
+    (74,  &["FK08", "KEY_F8", "F8"]),
+    (75,  &["FK09", "KEY_F9", "F9"]),
+    (76,  &["FK10", "KEY_F10", "F10"]),
+    (94,  &["LSGT", "KEY_102ND", "Less"]),
+    (95,  &["FK11", "KEY_F11", "F11"]),
+    (96,  &["FK12", "KEY_F12", "F12"]),
+    (97,  &["AB11", "KEY_RO", "Ro"]),
+    (100, &["HENK", "KEY_HENKAN", "Henkan"]),
+    (101, &["HKTG", "KEY_KATAKANAHIRAGANA", "Hiragana_Katakana"]),
+    (102, &["MUHE", "KEY_MUHENKAN", "Muhenkan"]),
+    (105, &["RCTL", "KEY_RIGHTCTRL", "Control_R"]),
+    (108, &["RALT", "KEY_RIGHTALT", "Alt_R", "ISO_Level3_Shift"]),
+    (110, &["HOME", "KEY_HOME", "Home"]),
+    (111, &["UP", "KEY_UP", "Up"]),
+    (112, &["PGUP", "KEY_PAGEUP", "Prior"]),
+    (113, &["LEFT", "KEY_LEFT", "Left"]),
+    (114, &["RGHT", "KEY_RIGHT", "Right"]),
+    (115, &["END", "KEY_END", "End"]),
+    (116, &["DOWN", "KEY_DOWN", "Down"]),
+    (117, &["PGDN", "KEY_PAGEDOWN", "Next"]),
+    (118, &["INS", "KEY_INSERT", "Insert"]),
+    (119, &["DELE", "KEY_DELETE", "Delete"]),
+    (130, &["HNGL", "KEY_HANGEUL", "Hangul"]),
+    (131, &["HJCV", "KEY_HANJA", "Hangul_Hanja"]),
+    (132, &["AE13", "KEY_YEN", "Yen"]),
+    (133, &["LWIN", "KEY_LEFTMETA", "Super_L"]),
+    (134, &["RWIN", "KEY_RIGHTMETA", "Super_R"]),
+    (135, &["COMP", "KEY_COMPOSE", "Menu"]),
+];
+
+/// Converts a key name, or a number, to its internal key code. Names are matched ignoring case.
+pub fn key_code(name: &str) -> Option<u16> {
+    if let Ok(key_code) = name.parse() {
+        return Some(key_code);
+    }
+
+    KEYS.iter()
+        .find(|(_, names)| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+        .map(|(key_code, _)| *key_code)
+}
+
+/// Name a key code is written as when layouts are generated, falls back to the number itself
+pub fn key_name(key_code: u16) -> String {
+    match KEYS.iter().find(|(code, _)| *code==key_code) {
+        Some((_, names)) => names[0].to_string(),
+        None => key_code.to_string(),
+    }
+}
+
+/// Key code written either as a number or as one of the names in `KEYS`
+#[derive(PartialEq, Eq, Hash)]
+struct KeyCode(u16);
+
+impl<'de> Deserialize<'de> for KeyCode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        struct KeyCodeVisitor;
+
+        impl<'de> Visitor<'de> for KeyCodeVisitor {
+            type Value = KeyCode;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a key code or a key name")
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<KeyCode, E> {
+                u16::try_from(value).map(KeyCode).map_err(|_| E::custom(format!("key code `{}` out of range", value)))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<KeyCode, E> {
+                u16::try_from(value).map(KeyCode).map_err(|_| E::custom(format!("key code `{}` out of range", value)))
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<KeyCode, E> {
+                key_code(value).map(KeyCode).ok_or_else(|| E::custom(format!("unknown key name `{}`", value)))
+            }
+        }
+
+        deserializer.deserialize_any(KeyCodeVisitor)
+    }
+}
+
+pub fn deserialize_key<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where D: Deserializer<'de> {
+    KeyCode::deserialize(deserializer).map(|key_code| key_code.0)
+}
+
+pub fn deserialize_map<'de, D, V>(deserializer: D) -> Result<HashMap<u16, V>, D::Error>
+where D: Deserializer<'de>, V: Deserialize<'de> {
+    let map: HashMap<KeyCode, V> = HashMap::deserialize(deserializer)?;
+    Ok(map.into_iter().map(|(key_code, value)| (key_code.0, value)).collect())
+}
+
+pub fn deserialize_option_map<'de, D, V>(deserializer: D) -> Result<Option<HashMap<u16, V>>, D::Error>
+where D: Deserializer<'de>, V: Deserialize<'de> {
+    let map: Option<HashMap<KeyCode, V>> = Option::deserialize(deserializer)?;
+    Ok(map.map(|map| map.into_iter().map(|(key_code, value)| (key_code.0, value)).collect()))
+}
+
+pub fn deserialize_set<'de, D>(deserializer: D) -> Result<HashSet<u16>, D::Error>
+where D: Deserializer<'de> {
+    let set: HashSet<KeyCode> = HashSet::deserialize(deserializer)?;
+    Ok(set.into_iter().map(|key_code| key_code.0).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Function;
+    use crate::methods::layout::parser::Layout;
+
+    fn layout(keys: &str) -> Result<Layout, zmerald::de::Error> {
+        zmerald::from_str(&format!(
+            "{{ id: Test, kind: Layout, modifiers: [ {{ kind: Set, key_codes: [ Shift_L, 62 ] }} ], levels: {{ 1: [], 2: [ 0 ] }}, {} }}",
+            keys,
+        ))
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(key_code("24"), Some(24));
+        assert_eq!(key_code("AD01"), Some(24));
+        assert_eq!(key_code("KEY_Q"), Some(24));
+        assert_eq!(key_code("space"), Some(65));
+        assert_eq!(key_code("RALT"), Some(108));
+        assert_eq!(key_code("KEY_NOPE"), None);
+    }
+
+    #[test]
+    fn named_keys_in_layouts() {
+        let layout = layout("keys: { AD01: [ q, Q ], 25: [ w ] }, bindings: { BACKSPACE: [ EmitKey(KEY_DELETE) ] }").unwrap();
+        assert_eq!(layout.modifiers[0].key_codes, HashSet::from([50, 62]));
+        assert_eq!(layout.keys[&24], vec![Some("q".to_string()), Some("Q".to_string())]);
+        assert_eq!(layout.keys[&25], vec![Some("w".to_string())]);
+        assert!(matches!(layout.bindings.unwrap()[&22].as_slice(), [Some(Function::EmitKey(119))]));
+    }
+
+    #[test]
+    fn unknown_key_names() {
+        let error = layout("keys: { KEY_NOPE: [ q ] }").err().unwrap();
+        assert!(error.to_string().contains("unknown key name `KEY_NOPE`"), "{}", error);
+        assert!(layout("keys: { 24: [ q ] }, bindings: { 22: [ EmitKey(Nope) ] }").is_err());
+    }
+}
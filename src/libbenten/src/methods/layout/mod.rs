@@ -1,16 +1,20 @@
 pub mod parser;
 pub use parser::*;
 
-use crate::{ BentenResponse, BentenError, Function };
+pub mod keys;
+
+use crate::{ BentenResponse, BentenError, Function, Width };
 use crate::methods::GenericMethodTrait;
 use std::collections::HashSet;
 use std::path::Path;
-use itertools::Itertools;
-use std::iter::FromIterator;
 
 pub struct LayoutMethod {
     pub layout: Layout,
     pub modifiers_pressed: HashSet<u16>,
+    pub latched: HashSet<u16>, //latch modifiers released along with the next key
+    /// Character held in preedit until we know whether a dead key will compose with it, as typed
+    /// and as shown once transformed by the modifiers
+    pub held: Option<(String, String)>,
 }
 
 //maybe rewrite GenericMethodTrait using actual Generics which will implement basic layout stuff, not like 
@@ -22,17 +26,21 @@ impl From<Layout> for LayoutMethod {
         LayoutMethod {
             layout,
             modifiers_pressed: HashSet::new(),
+            latched: HashSet::new(),
+            held: None,
         }
     }  
 }
 
 impl GenericMethodTrait for LayoutMethod {
     fn new(id: &str, path: &Path) -> Result<Self, BentenError> {
-        let layout: Layout = Layout::from_path(id, &path)?;
+        let layout: Layout = Layout::from_path(id, path)?;
 
         Ok(LayoutMethod {
             layout,
             modifiers_pressed: HashSet::new(),
+            latched: HashSet::new(),
+            held: None,
         })
     }
 
@@ -42,30 +50,21 @@ impl GenericMethodTrait for LayoutMethod {
             return BentenResponse::Undefined;
         }
             
-        let value = self.calculate_char(&key_code);
-
-        // Check for bindings
-        if let Some(bindings) = &self.layout().bindings {
-            if let Some(functions) = bindings.get(&key_code) {
-                for function in functions {
-                    match function {
-                        Some(Function::ChangeMethodTo(m)) => return BentenResponse::Function(Function::ChangeMethodTo(m.to_string())),
-                        _ => {},
-                    }
-                }
-            }
-        }
+        // composed before being transformed, as compose tables are keyed on the values of the layout
+        let value = self.calculate_value(&key_code);
 
         if let Some(value) = value {
-            BentenResponse::Commit(value)
+            self.compose(value)
         } else {
-            BentenResponse::Undefined
+            self.flush(BentenResponse::Undefined)
         }
     }
 
     fn on_key_release(&mut self, key_code: u16) -> BentenResponse {
         if let Some(modifier) = self.is_get_modifier(&key_code) {
             self.on_modifier_release(&modifier, &key_code);
+        } else {
+            self.release_latches();
         }
 
         BentenResponse::Undefined
@@ -75,14 +74,68 @@ impl GenericMethodTrait for LayoutMethod {
         &self.layout.id
     }
 
+    fn label(&self) -> &str {
+        self.layout.label.as_deref().unwrap_or(&self.layout.id)
+    }
+
+    fn width(&self) -> Option<Width> {
+        self.layout.width
+    }
+
+    fn bindings(&self, key_code: u16) -> Vec<Function> {
+        self.layout.bindings_of(key_code)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.held.is_none()
+    }
+
     fn reset(&mut self) {
+        self.held = None;
+    }
+
+    fn clear(&mut self) {
+        self.reset();
         self.modifiers_pressed.clear();
+        self.latched.clear();
+    }
+}
+
+impl LayoutMethod {
+    /// Combines `value` with the held character through the layout's compose table, holding `value`
+    /// in preedit instead when it could itself be composed with the next key.
+    fn compose(&mut self, value: String) -> BentenResponse {
+        if let Some((base, shown)) = self.held.take() {
+            if let Some(composed) = self.layout.compose(&base, &value) {
+                return BentenResponse::Commit(self.layout.transform(composed, &self.modifiers_pressed));
+            }
+
+            let response = self.compose(value);
+            return BentenResponse::CommitThen(shown, Box::new(response));
+        }
+
+        let shown = self.layout.transform(&value, &self.modifiers_pressed);
+        if self.layout.is_compose_base(&value) {
+            self.held = Some((value, shown.clone()));
+            BentenResponse::Suggest(shown)
+        } else {
+            BentenResponse::Commit(shown)
+        }
+    }
+
+    /// Commits the held character, if any, before `response`
+    fn flush(&mut self, response: BentenResponse) -> BentenResponse {
+        match self.held.take() {
+            Some((_, shown)) => BentenResponse::CommitThen(shown, Box::new(response)),
+            None => response
+        }
     }
 }
 
 pub trait LayoutHelper {
     fn layout(&mut self) -> &Layout;
     fn modifiers_pressed(&mut self) -> &mut HashSet<u16>;
+    fn latched(&mut self) -> &mut HashSet<u16>;
     fn layout_n_modifiers_pressed(&mut self) -> (&Layout, &mut HashSet<u16>);
 }
 
@@ -94,6 +147,10 @@ impl LayoutHelper for LayoutMethod {
     fn modifiers_pressed(&mut self) -> &mut HashSet<u16> {
         &mut self.modifiers_pressed
     } 
+
+    fn latched(&mut self) -> &mut HashSet<u16> {
+        &mut self.latched
+    }
        
     fn layout_n_modifiers_pressed(&mut self) -> (&Layout, &mut HashSet<u16>) {
         (&self.layout, &mut self.modifiers_pressed)
@@ -115,83 +172,62 @@ pub trait LayoutMethodTrait: LayoutHelper {
                 self.modifiers_pressed().extend(&modifier.key_codes)
             },
 
-            // remove from modifiers_pressed on ANY key press
-            ModifierKind::Latch => {}
+            // applies to the next key only, pressing it twice in a row locks it until pressed again
+            ModifierKind::Latch => {
+                if self.latched().remove(key_code) {
+                    return;
+                }
+
+                if !self.modifiers_pressed().remove(key_code) {
+                    self.modifiers_pressed().insert(*key_code);
+                    self.latched().insert(*key_code);
+                }
+            }
         } 
     }
 
+    /// Releases the latched modifiers, once the key they applied to is released
+    fn release_latches(&mut self) {
+        let latched = std::mem::take(self.latched());
+        self.modifiers_pressed().retain(|key_code| !latched.contains(key_code));
+    }
 
-    fn on_modifier_release(&mut self, modifier: &Modifier, key_code: &u16) {
-        match modifier.kind {
-            ModifierKind::Set => {
-                self.modifiers_pressed().remove(key_code);
-            },
 
-            _ => {}
-        } 
+    fn on_modifier_release(&mut self, modifier: &Modifier, key_code: &u16) {
+        if modifier.kind==ModifierKind::Set {
+            self.modifiers_pressed().remove(key_code);
+        }
     }
 
     fn calculate_level(&mut self) -> Option<usize> {
         let (layout, modifiers_pressed) = self.layout_n_modifiers_pressed();
 
-        if modifiers_pressed.is_empty() {
+        // a modifier with several key codes is held when any of them is
+        let modifiers = layout.modifiers_of(modifiers_pressed);
+        if modifiers==0 {
             return Some(0);
         }
 
-        for (level, modifier_indexes) in &layout.levels {
-            // if a modifier contains more than 1 keycode they are defined with an OR relationship, 
-            // therefore we shall split it into different modifiers when comparing, making it a 
-            // cartesian product.
-
-            //convert HashSet<ModifierIndex> -> Vec<HashSet<u16>> where u16: KeyCode
-            //optimisation: move this to the deserialisation phase
-            let modifiers: Vec<HashSet<u16>> = modifier_indexes.iter().map(|i| 
-                layout.modifiers[*i].key_codes.iter().copied()
-            ).multi_cartesian_product().map(HashSet::from_iter).collect();
-
-            for modifier in modifiers {
-                if *modifiers_pressed==modifier {
-                    return Some((level-1).into());
-                }
-            }
-        }
-
-        None
+        layout.levels.get(modifiers)
     }
 
     fn calculate_char(&mut self, key_code: &u16) -> Option<String> {
-        if let Some(level) = self.calculate_level() {
-            if let Some(key_code) = self.layout().keys.get(key_code) {
-                if let Some(character) = key_code.get(level) {
-                    if let Some(c) = character {
-                        return Some(c.to_owned());
-                    }
-                }
-            }
-        }
+        let value = self.calculate_value(key_code)?;
+        let (layout, modifiers_pressed) = self.layout_n_modifiers_pressed();
+        Some(layout.transform(&value, modifiers_pressed))
+    }
 
-        None
+    /// Value of `key_code` at the level of the modifiers held, before their transforms
+    fn calculate_value(&mut self, key_code: &u16) -> Option<String> {
+        let level = self.calculate_level();
+        let layout = self.layout();
+        layout.value_at(layout.keys.get(key_code)?, level).cloned()
     }
 
     fn calculate_special_key(&mut self, key_code: &u16) -> Option<String> {
-        if let Some(level) = self.calculate_level() {
-            if let Some(special_keys) = &self.layout().specs {
-                if let Some(value_opt) = special_keys.get(key_code) {
-                    if let Some(value) = value_opt.get(level) {
-                        if let Some(v) = value {
-                            // Rely on last previous non null value, allowing special keys to be used when modifiers are pressed
-                            // while opt.is_none() {
-                            //     opt = value_opt.get(level-1);
-                            // }
-
-                            return Some(v.to_owned());
-                        }
-                    }
-                }
-            }
-        }
-
-        None    
+        let level = self.calculate_level();
+        let layout = self.layout();
+        layout.value_at(layout.specs.as_ref()?.get(key_code)?, level).cloned()
     }
 
     fn is_get_modifier(&mut self, key_code: &u16) -> Option<Modifier> {
@@ -203,16 +239,105 @@ pub trait LayoutMethodTrait: LayoutHelper {
 
         None
     }
+}
 
-    fn is(&mut self, key_code: &u16, name: &str) -> bool {
-        if let Some(key) = self.calculate_special_key(key_code) {
-            if key==name {
-                return true;
-            }
-        }
+impl LayoutMethodTrait for LayoutMethod {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Transform;
+    use std::collections::HashMap;
+
+    fn layout() -> LayoutMethod {
+        LayoutMethod::from(Layout {
+            id: "test".to_string(),
+            kind: LayoutKind::Layout,
+            label: None,
+            modifiers: vec![Modifier { kind: ModifierKind::Latch, key_codes: HashSet::from([50]), transform: None }],
+            levels: Levels::new([(1, HashSet::new()), (2, HashSet::from([0]))]),
+            specs: None,
+            keys: HashMap::from([
+                (38, vec![Some("a".to_string()), Some("A".to_string())]),
+                (39, vec![Some("s".to_string())]),
+            ]),
+            bindings: None,
+            compose: None,
+            table: None,
+            fallback: Fallback::PassThrough,
+            width: None,
+        })
+    }
 
-        false
+    fn type_key(method: &mut LayoutMethod, key_code: u16) -> BentenResponse {
+        let response = method.on_key_press(key_code);
+        method.on_key_release(key_code);
+        response
     }
-}
 
-impl LayoutMethodTrait for LayoutMethod {}
\ No newline at end of file
+    #[test]
+    fn latch_then_lock() {
+        let mut method = layout();
+        let commit = |value: &str| BentenResponse::Commit(value.to_string());
+
+        type_key(&mut method, 50);
+        assert_eq!(type_key(&mut method, 38), commit("A"));
+        assert_eq!(type_key(&mut method, 38), commit("a"));
+
+        type_key(&mut method, 50);
+        type_key(&mut method, 50);
+        assert_eq!(type_key(&mut method, 38), commit("A"));
+        assert_eq!(type_key(&mut method, 38), commit("A"));
+
+        type_key(&mut method, 50);
+        assert_eq!(type_key(&mut method, 38), commit("a"));
+    }
+
+    #[test]
+    fn fallback_to_base() {
+        let mut method = layout();
+        type_key(&mut method, 50);
+        assert_eq!(type_key(&mut method, 39), BentenResponse::Undefined);
+
+        method.layout.fallback = Fallback::Base;
+        type_key(&mut method, 50);
+        assert_eq!(type_key(&mut method, 39), BentenResponse::Commit("s".to_string()));
+    }
+
+    #[test]
+    fn transform_modifier() {
+        let mut method = layout();
+        method.layout.modifiers.push(Modifier { kind: ModifierKind::Lock, key_codes: HashSet::from([66]), transform: Some(Transform::Fullwidth) });
+
+        type_key(&mut method, 66);
+        assert_eq!(type_key(&mut method, 38), BentenResponse::Commit("ａ".to_string()));
+        type_key(&mut method, 50);
+        assert_eq!(type_key(&mut method, 38), BentenResponse::Commit("Ａ".to_string()));
+    }
+
+    #[test]
+    fn compose_then_transform() {
+        let mut method = layout();
+        method.layout.modifiers.push(Modifier { kind: ModifierKind::Lock, key_codes: HashSet::from([66]), transform: Some(Transform::Katakana) });
+        method.layout.compose = Some(HashMap::from([("゙".to_string(), HashMap::from([("か".to_string(), "が".to_string())]))]));
+        method.layout.keys.extend([(39, vec![Some("か".to_string())]), (34, vec![Some("゙".to_string())])]);
+
+        type_key(&mut method, 66);
+        assert_eq!(type_key(&mut method, 39), BentenResponse::Suggest("カ".to_string()));
+        assert_eq!(type_key(&mut method, 34), BentenResponse::Commit("ガ".to_string()));
+
+        //the held character is committed as it was shown
+        type_key(&mut method, 39);
+        type_key(&mut method, 66);
+        assert_eq!(type_key(&mut method, 38), BentenResponse::CommitThen("カ".to_string(), Box::new(BentenResponse::Commit("a".to_string()))));
+    }
+
+    #[test]
+    fn levels_by_modifiers() {
+        let levels = Levels::new([(1, HashSet::new()), (3, HashSet::from([0, 1])), (2, HashSet::from([1, 0])), (4, HashSet::from([1]))]);
+        assert_eq!(levels.get(0b00), Some(0));
+        assert_eq!(levels.get(0b11), Some(1));
+        assert_eq!(levels.get(0b10), Some(3));
+        assert_eq!(levels.get(0b01), None);
+    }
+}
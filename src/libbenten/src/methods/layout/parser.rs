@@ -1,33 +1,118 @@
-use serde::Deserialize;
+use serde::{ Deserialize, Deserializer };
 use std::fs::File;
 use std::io::BufReader;
 use std::path::Path;
-use std::collections::{ HashMap, HashSet };
+use std::collections::{ BTreeMap, HashMap, HashSet };
 
-use crate::{ Function, BentenError };
+use crate::{ Function, BentenError, Transform, Width };
+use super::keys;
+use crate::methods::table::TableOptions;
 
 #[derive(Deserialize)]
 pub struct Layout {
     pub id: String,
     pub kind: LayoutKind,
+    #[serde(default)]
+    pub label: Option<String>,                               //shown by frontends while the method is in use
     
     pub modifiers: Vec<Modifier>,                            //will use `Name` tag to associate levels with modifiers on deserialise 
-    pub levels: HashMap<u16, HashSet<ModifierIndex>>,        //<Level, Modifiers> # pointing to layout.modifiers
+    pub levels: Levels,                                      //<Level, Modifiers> # pointing to layout.modifiers
 
+    // key codes can be written as numbers or as key names, see `keys.rs`
+    #[serde(default, deserialize_with = "keys::deserialize_option_map")]
     pub specs: Option<HashMap<u16, Vec<Option<String>>>>,    //<KeyCode, SpecialName>
+    #[serde(deserialize_with = "keys::deserialize_map")]
     pub keys: HashMap<u16, Vec<Option<String>>>,            //<KeyCode, Character.s>
-    pub bindings: Option<HashMap<u16, Vec<Option<Function>>>>         //<KeyCode, Functions>
+    #[serde(default, deserialize_with = "deserialize_bindings")]
+    pub bindings: Option<Bindings>,                          //<KeyCode, Functions>
+    pub compose: Option<HashMap<String, HashMap<String, String>>>,   //<DeadKey, <Base, Composed>>
+    #[serde(default)]
+    pub table: Option<TableOptions>,                         //only read by table methods
+    #[serde(default)]
+    pub fallback: Fallback,                                  //for keys with no value at the level of the modifiers held
+    #[serde(default)]
+    pub width: Option<Width>,                                //of what the method commits and suggests, see `Function::ToggleWidth`
 }
 
-#[derive(Deserialize)]
+/// What a key with no value at the level of the modifiers held does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum Fallback {
+    /// Left to the application
+    #[default]
+    PassThrough,
+    /// Its value at the first level
+    Base,
+    /// Its value at the given level
+    Level(u16),
+}
+
+#[derive(Debug, Deserialize)]
 pub enum LayoutKind {
     Layout,
     Table,
+    Unicode,
     Hangeul,
 }
 
 pub type ModifierIndex = usize;
 
+/// Levels of a layout, compiled when read into the level of each combination of modifiers
+#[derive(Debug, Clone, Default)]
+pub struct Levels {
+    levels: BTreeMap<u16, HashSet<ModifierIndex>>,
+    by_modifiers: HashMap<u64, usize>,                      //<Bitset of ModifierIndex, index of the level in `keys`>
+}
+
+impl Levels {
+    /// Compiles `levels`, the lowest of those defined with the same modifiers taking precedence
+    pub fn new(levels: impl IntoIterator<Item = (u16, HashSet<ModifierIndex>)>) -> Self {
+        let levels: BTreeMap<_, _> = levels.into_iter().collect();
+
+        let mut by_modifiers = HashMap::new();
+        for (level, modifier_indexes) in &levels {
+            if *level==0 || modifier_indexes.iter().any(|i| *i>=u64::BITS as usize) {
+                log::warn!("level {} is ignored, levels start at 1 and can use the first {} modifiers", level, u64::BITS);
+                continue;
+            }
+
+            let modifiers = modifier_indexes.iter().fold(0u64, |modifiers, i| modifiers | 1<<i);
+            by_modifiers.entry(modifiers).or_insert(*level as usize-1);
+        }
+
+        Self { levels, by_modifiers }
+    }
+
+    /// Index in `keys` of the level of the modifiers set in the bitset `modifiers`
+    pub fn get(&self, modifiers: u64) -> Option<usize> {
+        self.by_modifiers.get(&modifiers).copied()
+    }
+
+    /// Levels with their modifiers, by level
+    pub fn iter(&self) -> impl Iterator<Item = (&u16, &HashSet<ModifierIndex>)> {
+        self.levels.iter()
+    }
+}
+
+impl<'de> Deserialize<'de> for Levels {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where D: Deserializer<'de> {
+        HashMap::<u16, HashSet<ModifierIndex>>::deserialize(deserializer).map(Levels::new)
+    }
+}
+
+/// Functions bound to each key code, run one after the other
+pub type Bindings = HashMap<u16, Vec<Option<Function>>>;
+
+fn deserialize_bindings<'de, D>(deserializer: D) -> Result<Option<Bindings>, D::Error>
+where D: Deserializer<'de> {
+    let bindings: Option<Bindings> = keys::deserialize_option_map(deserializer)?;
+    for functions in bindings.iter().flat_map(HashMap::values) {
+        crate::check_sequence(functions.iter().flatten()).map_err(serde::de::Error::custom)?;
+    }
+
+    Ok(bindings)
+}
+
 /* ???
 pub type KeyCodes = HashSet<u16>;
 pub enum Modifier {
@@ -40,7 +125,11 @@ pub enum Modifier {
 #[derive(Debug, Clone, Deserialize)]
 pub struct Modifier {
     pub kind: ModifierKind,
-    pub key_codes: HashSet<u16>
+    #[serde(deserialize_with = "keys::deserialize_set")]
+    pub key_codes: HashSet<u16>,
+    /// Applied to the values of keys while held, in which case the modifier takes no part in levels
+    #[serde(default)]
+    pub transform: Option<Transform>,
 }
 
 #[derive(Debug, Hash, Clone, Copy, PartialEq, Deserialize)]
@@ -55,6 +144,154 @@ impl Layout {
         let path = base_dir.join("layouts").join(id).with_extension("layout.zm");
         let file = File::open(path)?;
         let reader = BufReader::new(file);
-        Ok(zmerald::from_reader(reader).unwrap())
+        Ok(zmerald::from_reader(reader)?)
+    }
+
+    /// Writes the layout in the `.layout.zm` format, with keys named as in `keys.rs`
+    pub fn to_zm(&self) -> String {
+        let mut out = format!("{{\n\tid: {},\n\tkind: {:?},\n", quote(&self.id), self.kind);
+        if let Some(label) = &self.label {
+            out.push_str(&format!("\tlabel: {},\n", quote(label)));
+        }
+
+        if self.specs.is_none() {
+            out.push_str("\tspecs: None,\n");
+        }
+
+        if self.bindings.is_none() {
+            out.push_str("\tbindings: None,\n");
+        }
+
+        out.push_str("\n\tmodifiers: [\n");
+        let modifiers: Vec<String> = self.modifiers.iter().map(|modifier| {
+            let mut key_codes: Vec<&u16> = modifier.key_codes.iter().collect();
+            key_codes.sort();
+            let key_codes: Vec<String> = key_codes.into_iter().map(|key_code| keys::key_name(*key_code)).collect();
+            match modifier.transform {
+                Some(transform) => format!("\t\t{{ kind: {:?}, key_codes: [ {} ], transform: {:?} }}", modifier.kind, key_codes.join(", "), transform),
+                None => format!("\t\t{{ kind: {:?}, key_codes: [ {} ] }}", modifier.kind, key_codes.join(", ")),
+            }
+        }).collect();
+        out.push_str(&modifiers.join(",\n"));
+        out.push_str("\n\t],\n");
+
+        out.push_str("\n\tlevels: {\n");
+        for (level, modifier_indexes) in self.levels.iter() {
+            let mut modifier_indexes: Vec<String> = modifier_indexes.iter().map(|i| i.to_string()).collect();
+            modifier_indexes.sort();
+            if modifier_indexes.is_empty() {
+                out.push_str(&format!("\t\t{}: [],\n", level));
+            } else {
+                out.push_str(&format!("\t\t{}: [ {} ],\n", level, modifier_indexes.join(", ")));
+            }
+        }
+        out.push_str("\t},\n");
+
+        write_key_map(&mut out, "specs", self.specs.as_ref(), |value| value.as_deref().map_or("None".to_string(), quote));
+        write_key_map(&mut out, "keys", Some(&self.keys), |value| value.as_deref().map_or("None".to_string(), quote));
+        write_key_map(&mut out, "bindings", self.bindings.as_ref(), |function| match function {
+            Some(function) => format!("{:?}", function),
+            None => "None".to_string(),
+        });
+
+        if let Some(compose) = &self.compose {
+            out.push_str("\n\tcompose: {\n");
+            for (dead_key, bases) in compose {
+                let bases: Vec<String> = bases.iter().map(|(base, composed)| format!("{}: {}", quote(base), quote(composed))).collect();
+                out.push_str(&format!("\t\t{}: {{ {} }},\n", quote(dead_key), bases.join(", ")));
+            }
+            out.push_str("\t},\n");
+        }
+
+        if let Some(table) = &self.table {
+            out.push_str(&format!("\n\ttable: {{ learn: {}, lookup: {:?}, auto_commit: {}", table.learn, table.lookup, table.auto_commit));
+            if let Some(max_length) = table.max_length {
+                out.push_str(&format!(", max_length: {}", max_length));
+            }
+            out.push_str(" },\n");
+        }
+
+        if self.fallback!=Fallback::PassThrough {
+            out.push_str(&format!("\n\tfallback: {:?},\n", self.fallback));
+        }
+
+        if let Some(width) = self.width {
+            out.push_str(&format!("\n\twidth: {:?},\n", width));
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    /// Value among the `values` of a key at `level`, the index of a level in `keys`, or as `fallback` says
+    pub fn value_at<'a>(&self, values: &'a [Option<String>], level: Option<usize>) -> Option<&'a String> {
+        let value = level.and_then(|level| values.get(level)).and_then(Option::as_ref);
+        value.or_else(|| match self.fallback {
+            Fallback::PassThrough => None,
+            Fallback::Base => values.first()?.as_ref(),
+            Fallback::Level(level) => values.get((level as usize).checked_sub(1)?)?.as_ref(),
+        })
+    }
+
+    /// Bitset of the modifiers, by index, with any of their keys among `key_codes`, leaving out
+    /// those applying a transform
+    pub fn modifiers_of(&self, key_codes: &HashSet<u16>) -> u64 {
+        self.modifiers.iter().enumerate()
+            .filter(|(_, modifier)| modifier.transform.is_none() && modifier.key_codes.iter().any(|key_code| key_codes.contains(key_code)))
+            .fold(0, |modifiers, (i, _)| modifiers | 1<<i)
+    }
+
+    /// `value` transformed by the modifiers with any of their keys among `key_codes`, in order
+    pub fn transform(&self, value: &str, key_codes: &HashSet<u16>) -> String {
+        self.modifiers.iter()
+            .filter(|modifier| modifier.key_codes.iter().any(|key_code| key_codes.contains(key_code)))
+            .filter_map(|modifier| modifier.transform)
+            .fold(value.to_string(), |value, transform| transform.apply(&value))
+    }
+
+    /// Functions bound to `key_code`
+    pub fn bindings_of(&self, key_code: u16) -> Vec<Function> {
+        match self.bindings.as_ref().and_then(|bindings| bindings.get(&key_code)) {
+            Some(functions) => functions.iter().flatten().cloned().collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Looks up the precomposed character produced by typing `dead_key` after `base`
+    pub fn compose(&self, base: &str, dead_key: &str) -> Option<&String> {
+        self.compose.as_ref()?.get(dead_key)?.get(base)
+    }
+
+    /// Whether `value` may combine with a dead key typed after it
+    pub fn is_compose_base(&self, value: &str) -> bool {
+        match &self.compose {
+            Some(compose) => compose.values().any(|bases| bases.contains_key(value)),
+            None => false
+        }
+    }
+}
+
+fn write_key_map<V>(out: &mut String, name: &str, map: Option<&HashMap<u16, Vec<V>>>, format: impl Fn(&V) -> String) {
+    let map = match map {
+        Some(map) => map,
+        None => return,
+    };
+
+    out.push_str(&format!("\n\t{}: {{\n", name));
+    let mut key_codes: Vec<&u16> = map.keys().collect();
+    key_codes.sort();
+    for key_code in key_codes {
+        let values: Vec<String> = map[key_code].iter().map(&format).collect();
+        out.push_str(&format!("\t\t{}: [ {} ],\n", keys::key_name(*key_code), values.join(", ")));
     }
-}
\ No newline at end of file
+    out.push_str("\t},\n");
+}
+
+/// Quotes strings which would not be read back as they are when left bare
+fn quote(value: &str) -> String {
+    if !value.is_empty() && value!="None" && value.chars().all(|c| c.is_alphanumeric() || c=='_') {
+        return value.to_string();
+    }
+
+    format!("{:?}", value)
+}
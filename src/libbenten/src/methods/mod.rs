@@ -4,8 +4,11 @@ use layout::{ Layout, LayoutMethod, LayoutKind };
 pub mod table;
 use table::TableMethod;
 
-use crate::{ BentenResponse, BentenError };
-use serde::{ Deserialize, Deserializer };
+pub mod unicode;
+use unicode::UnicodeMethod;
+
+use crate::{ BentenResponse, BentenError, Function, Width };
+use serde::Deserialize;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
@@ -16,16 +19,51 @@ pub trait GenericMethodTrait {
     fn on_key_press(&mut self, key_code: u16) -> BentenResponse;
     fn on_key_release(&mut self, key_code: u16) -> BentenResponse;
     fn id(&self) -> &str;
+    /// Name of the method shown by frontends, its id when it has none
+    fn label(&self) -> &str;
+    /// Width its output is converted to unless toggled, see `State::widths`
+    fn width(&self) -> Option<Width>;
+    /// Drops what is being typed, modifiers locked or latched staying so
     fn reset(&mut self);
+    /// Drops what is being typed and releases every modifier
+    fn clear(&mut self);
+
+    /// Functions bound to `key_code`, which the engine runs after passing it the key
+    fn bindings(&self, key_code: u16) -> Vec<Function>;
+    /// Whether nothing is being typed, see `IfEmptyChangeMethodTo`
+    fn is_empty(&self) -> bool;
+
+    /// Values currently offered along with the key sequence of each
+    fn candidates(&self) -> Vec<(String, String)> {
+        Vec::new()
+    }
+
+    /// Key sequences producing `value`, for methods backed by a table
+    fn reverse_lookup(&self, _value: &str) -> Vec<String> {
+        Vec::new()
+    }
 }
 
-#[derive(Deserialize)]
+/// How many methods switched away from are remembered to return to
+pub const MAX_METHOD_STACK: usize = 8;
+
 pub struct State {
-    #[serde(skip)]
     pub id: String,
-    #[serde(deserialize_with = "from_methods")]
     pub methods: HashMap<String, Box<dyn GenericMethodTrait>>,
     pub current_method: String,
+    pub method_stack: Vec<String>, //methods switched away from, the most recent last, at most `MAX_METHOD_STACK`
+    pub widths: HashMap<String, Option<Width>>, //of the methods whose width was toggled
+    pub one_shot: Option<String>, //method switched to with `OneShot`, left after its next commit
+    pub reverse_lookup: Option<String>, //method whose key sequences are shown after committing with the others
+}
+
+/// Layout file defining several methods, which are read relative to the same directory
+#[derive(Deserialize)]
+struct StateFile {
+    methods: Vec<Layout>,
+    current_method: String,
+    #[serde(default)]
+    reverse_lookup: Option<String>,
 }
 
 impl State {
@@ -34,58 +72,61 @@ impl State {
         let file = File::open(&path)?;
         let reader = BufReader::new(file);
 
-        match zmerald::de::from_reader::<_, State>(reader) {
-            Ok(mut g) => {
-                g.id = id.to_string();
-                return Ok(g);
+        match zmerald::de::from_reader::<_, StateFile>(reader) {
+            Ok(file) => {
+                let mut methods: HashMap<String, Box<dyn GenericMethodTrait>> = HashMap::new();
+                for layout in file.methods {
+                    if let Some(method) = from_layout(layout, base_dir)? {
+                        methods.insert(method.id().to_string(), method);
+                    }
+                }
+
+                let mut state = State::from(id, methods, file.current_method);
+                state.reverse_lookup = file.reverse_lookup;
+                Ok(state)
             },
 
             // this allows the config to define a single method not within a global struct, simpler single methods
             Err(e) => {
-                if let Ok(table) = TableMethod::new(id, &base_dir) {
-                    return Ok(State::from(id, Box::new(table)));
+                if let Ok(table) = TableMethod::new(id, base_dir) {
+                    Ok(State::single(id, Box::new(table)))
                 } else {
-                    match LayoutMethod::new(id, &base_dir) {
-                        Ok(layout) => return Ok(State::from(id, Box::new(layout))),
-                        Err(_) => panic!("{}", e)//return Err(BentenError::ZmeraldError(e))
+                    match Layout::from_path(id, base_dir) {
+                        Ok(layout) if matches!(layout.kind, LayoutKind::Unicode) => Ok(State::single(id, Box::new(UnicodeMethod::from(layout)))),
+                        Ok(layout) => Ok(State::single(id, Box::new(LayoutMethod::from(layout)))),
+                        Err(_) => Err(BentenError::ZmeraldError(e)),
                     }
-                }     
+                }
             }
         }
     }
 }
 
 impl State {
-    fn from(id: &str, method: Box<dyn GenericMethodTrait>) -> Self {
-        let current_method = method.id().to_string();
-        let mut methods = HashMap::new();
-        methods.insert(current_method.clone(), method);
+    fn from(id: &str, methods: HashMap<String, Box<dyn GenericMethodTrait>>, current_method: String) -> Self {
         Self {
             id: id.to_string(),
             methods,
-            current_method: current_method,
+            current_method,
+            method_stack: Vec::new(),
+            widths: HashMap::new(),
+            one_shot: None,
+            reverse_lookup: None,
         }
     }
-}
 
-fn from_methods<'de, D>(deserializer: D) -> Result<HashMap<String, Box<dyn GenericMethodTrait>>, D::Error>
-where D: Deserializer<'de> {
-    let values: Vec<Layout> = Vec::deserialize(deserializer)?;
-
-    let mut out: HashMap<String, Box<dyn GenericMethodTrait>> = HashMap::new();
-    for value in values {
-        match value.kind {
-            LayoutKind::Layout => { out.insert(value.id.to_string(), Box::new(LayoutMethod::from(value))); },
-            LayoutKind::Table => {
-                let value_name = value.id.to_string();
-                out.insert(value_name, Box::new(TableMethod::try_from(value).unwrap()));
-                // if let Ok(table_method) = TableMethod::try_from(value) {
-                //     out.insert(value_name, Box::new(table_method));
-                // }
-            },
-            LayoutKind::Hangeul => {}
-        }
+    fn single(id: &str, method: Box<dyn GenericMethodTrait>) -> Self {
+        let current_method = method.id().to_string();
+        State::from(id, HashMap::from([(current_method.clone(), method)]), current_method)
     }
+}
 
-    Ok(out)
-}
\ No newline at end of file
+/// Method defined by `layout`, reading what it needs relative to `base_dir`
+fn from_layout(layout: Layout, base_dir: &Path) -> Result<Option<Box<dyn GenericMethodTrait>>, BentenError> {
+    Ok(match layout.kind {
+        LayoutKind::Layout => Some(Box::new(LayoutMethod::from(layout))),
+        LayoutKind::Table => Some(Box::new(TableMethod::from_layout(layout, base_dir)?)),
+        LayoutKind::Unicode => Some(Box::new(UnicodeMethod::from(layout))),
+        LayoutKind::Hangeul => None,
+    })
+}
@@ -0,0 +1,311 @@
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::{ BufWriter, Write };
+use std::ops::Deref;
+use std::path::{ Path, PathBuf };
+use std::time::UNIX_EPOCH;
+
+use super::{ Entry, Table };
+
+/// Compiled tables, `$XDG_CACHE_HOME/benten/tables/<id>-<hash of the source path>.bin` so that
+/// tables of the same id in different directories keep their own, laid out as follows with every
+/// number little endian and every string a `u32` length followed by its bytes:
+/// - `MAGIC`, `VERSION`;
+/// - the source it was compiled from: path, length (`u64`), modification time (`u64` seconds, `u32` nanoseconds);
+/// - the entries as described in `Entries`, which are read in place from the mapped file.
+const MAGIC: &[u8; 8] = b"BENTENTB";
+const VERSION: u32 = 2;
+const NO_WEIGHT: u32 = u32::MAX;
+
+/// Source of a table as recorded in its compiled form, which is rebuilt when any of it changes
+#[derive(PartialEq)]
+struct Source {
+	path: String,
+	len: u64,
+	secs: u64,
+	nanos: u32,
+}
+
+impl Source {
+	fn of(path: &Path) -> Option<Self> {
+		let metadata = std::fs::metadata(path).ok()?;
+		let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+		Some(Source {
+			path: path.to_str()?.to_string(),
+			len: metadata.len(),
+			secs: modified.as_secs(),
+			nanos: modified.subsec_nanos(),
+		})
+	}
+}
+
+enum Data {
+	Owned(Vec<u8>),
+	Mapped(Mmap),
+}
+
+impl Deref for Data {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		match self {
+			Data::Owned(data) => data,
+			Data::Mapped(data) => data,
+		}
+	}
+}
+
+/// Entries of a table in their compiled form, laid out as:
+/// - the number of entries (`u32`);
+/// - the offset of each entry from the start of the first one (`u32`);
+/// - the entries sorted by sequence, to find those starting with a sequence (`u32`);
+/// - each entry: sequence, value, weight (`u32`, `NO_WEIGHT` for none).
+pub struct Entries {
+	data: Data,
+	start: usize, //of the entries in `data`, after the header of a cache
+	len: usize,
+}
+
+impl Entries {
+	pub fn new(entries: &[Entry]) -> Self {
+		let mut index: Vec<u32> = (0..entries.len() as u32).collect();
+		index.sort_by(|a, b| entries[*a as usize].sequence.cmp(&entries[*b as usize].sequence));
+
+		let mut offsets = Vec::with_capacity(entries.len());
+		let mut records = Vec::new();
+		for entry in entries {
+			offsets.push(records.len() as u32);
+			push_str(&mut records, &entry.sequence);
+			push_str(&mut records, &entry.value);
+			records.extend(entry.weight.unwrap_or(NO_WEIGHT).to_le_bytes());
+		}
+
+		let mut data = Vec::with_capacity(4+8*entries.len()+records.len());
+		data.extend((entries.len() as u32).to_le_bytes());
+		offsets.iter().chain(&index).for_each(|i| data.extend(i.to_le_bytes()));
+		data.extend(records);
+
+		Entries { data: Data::Owned(data), start: 0, len: entries.len() }
+	}
+
+	/// Entries found at `start` of `data`, if they are all complete
+	fn read(data: Data, start: usize) -> Option<Self> {
+		let len = Reader { data: data.get(start..)? }.u32()? as usize;
+		let entries = Entries { data, start, len };
+		if entries.records()>entries.data.len() {
+			return None;
+		}
+
+		for i in 0..len {
+			let mut record = Reader { data: entries.data.get(entries.records()+entries.offset(i)..)? };
+			record.str()?;
+			record.str()?;
+			record.u32()?;
+			if entries.sorted(i)>=len {
+				return None;
+			}
+		}
+
+		Some(entries)
+	}
+
+	pub fn len(&self) -> usize {
+		self.len
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len==0
+	}
+
+	pub fn sequence(&self, i: usize) -> &str {
+		self.record(i).str().unwrap_or_default()
+	}
+
+	pub fn value(&self, i: usize) -> &str {
+		let mut record = self.record(i);
+		record.str();
+		record.str().unwrap_or_default()
+	}
+
+	pub fn get(&self, i: usize) -> Entry {
+		let mut record = self.record(i);
+		let sequence = record.str().unwrap_or_default().to_string();
+		let value = record.str().unwrap_or_default().to_string();
+		let weight = record.u32().filter(|weight| *weight!=NO_WEIGHT);
+		Entry { value, sequence, weight }
+	}
+
+	/// Index of the `i`th entry by sequence
+	pub fn sorted(&self, i: usize) -> usize {
+		self.u32_at(self.start+4+4*(self.len+i)) as usize
+	}
+
+	fn offset(&self, i: usize) -> usize {
+		self.u32_at(self.start+4+4*i) as usize
+	}
+
+	fn records(&self) -> usize {
+		self.start+4+8*self.len
+	}
+
+	fn record(&self, i: usize) -> Reader<'_> {
+		Reader { data: &self.data[self.records()+self.offset(i)..] }
+	}
+
+	fn u32_at(&self, pos: usize) -> u32 {
+		u32::from_le_bytes(self.data[pos..pos+4].try_into().unwrap())
+	}
+
+	fn bytes(&self) -> &[u8] {
+		&self.data[self.start..]
+	}
+}
+
+impl std::fmt::Debug for Entries {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("Entries").field("len", &self.len).finish()
+	}
+}
+
+impl PartialEq for Entries {
+	fn eq(&self, other: &Self) -> bool {
+		self.bytes()==other.bytes()
+	}
+}
+
+fn cache_path(id: &str, source: &Path) -> Option<PathBuf> {
+	let base_dirs = xdg::BaseDirectories::with_prefix("benten").ok()?;
+	let name = format!("{}-{:016x}", id, hash(source.to_str()?.as_bytes()));
+	Some(base_dirs.get_cache_home().join("tables").join(name).with_extension("bin"))
+}
+
+/// FNV-1a, which unlike the hasher of the standard library is the same across builds
+fn hash(bytes: &[u8]) -> u64 {
+	bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+/// Compiled form of the table read from `source`, if it is cached and up to date
+pub fn load(id: &str, source: &Path) -> Option<Table> {
+	let file = File::open(cache_path(id, source)?).ok()?;
+	// SAFETY: caches are only ever replaced by renaming a new file over them, never written in place
+	let data = unsafe { Mmap::map(&file) }.ok()?;
+
+	let table = decode(id, Data::Mapped(data), &Source::of(source)?);
+	if table.is_none() {
+		log::info!("table `{}` changed, compiling it again", id);
+	}
+
+	table
+}
+
+/// Compiles `table`, read from `source`, into the cache
+pub fn store(table: &Table, source: &Path) -> std::io::Result<()> {
+	let (path, source) = match (cache_path(&table.id, source), Source::of(source)) {
+		(Some(path), Some(source)) => (path, source),
+		_ => return Ok(()),
+	};
+
+	std::fs::create_dir_all(path.parent().unwrap())?;
+	let tmp_path = path.with_extension("bin.tmp");
+	let mut out = BufWriter::new(File::create(&tmp_path)?);
+
+	out.write_all(&encode(table, &source))?;
+	out.into_inner()?.sync_all()?;
+	std::fs::rename(tmp_path, path)
+}
+
+fn encode(table: &Table, source: &Source) -> Vec<u8> {
+	let mut out = MAGIC.to_vec();
+	out.extend(VERSION.to_le_bytes());
+	push_str(&mut out, &source.path);
+	out.extend(source.len.to_le_bytes());
+	out.extend(source.secs.to_le_bytes());
+	out.extend(source.nanos.to_le_bytes());
+
+	out.extend(table.entries.bytes());
+	out
+}
+
+fn push_str(out: &mut Vec<u8>, value: &str) {
+	out.extend((value.len() as u32).to_le_bytes());
+	out.extend(value.as_bytes());
+}
+
+fn decode(id: &str, data: Data, source: &Source) -> Option<Table> {
+	let mut reader = Reader { data: &data };
+	if reader.bytes(MAGIC.len())!=MAGIC || reader.u32()?!=VERSION {
+		return None;
+	}
+
+	let cached = Source { path: reader.str()?.to_string(), len: reader.u64()?, secs: reader.u64()?, nanos: reader.u32()? };
+	if cached!=*source {
+		return None;
+	}
+
+	let start = data.len()-reader.data.len();
+	Some(Table { id: id.to_string(), entries: Entries::read(data, start)? })
+}
+
+/// Reads the compiled form from the start, every read failing once the data runs out
+struct Reader<'a> {
+	data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+	fn bytes(&mut self, len: usize) -> &'a [u8] {
+		let len = len.min(self.data.len());
+		let (bytes, rest) = self.data.split_at(len);
+		self.data = rest;
+		bytes
+	}
+
+	fn u32(&mut self) -> Option<u32> {
+		Some(u32::from_le_bytes(self.bytes(4).try_into().ok()?))
+	}
+
+	fn u64(&mut self) -> Option<u64> {
+		Some(u64::from_le_bytes(self.bytes(8).try_into().ok()?))
+	}
+
+	fn str(&mut self) -> Option<&'a str> {
+		let len = self.u32()? as usize;
+		let bytes = self.bytes(len);
+		if bytes.len()!=len {
+			return None;
+		}
+
+		std::str::from_utf8(bytes).ok()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let entries = vec![
+			Entry { value: "明".to_string(), sequence: "ab".to_string(), weight: None },
+			Entry { value: "日".to_string(), sequence: "a".to_string(), weight: Some(10) },
+		];
+		let table = Table::new("test", entries.clone());
+		let source = Source { path: "/tables/test.dict".to_string(), len: 20, secs: 1, nanos: 2 };
+
+		let data = encode(&table, &source);
+		let decoded = decode("test", Data::Owned(data.clone()), &source).unwrap();
+		assert_eq!(decoded, table);
+		assert_eq!((0..2).map(|i| decoded.entries.get(i)).collect::<Vec<_>>(), entries);
+		assert_eq!(decoded.entries.sorted(0), 1);
+
+		assert_eq!(decode("test", Data::Owned(data[..data.len()-1].to_vec()), &source), None);
+		assert_eq!(decode("test", Data::Owned(data), &Source { len: 21, ..source }), None);
+	}
+
+	#[test]
+	fn path_by_source() {
+		crate::test_dirs();
+		let path = cache_path("test", Path::new("/tables/test.dict")).unwrap();
+		assert_ne!(path, cache_path("test", Path::new("/other/tables/test.dict")).unwrap());
+		assert!(path.starts_with(std::env::temp_dir()));
+	}
+}
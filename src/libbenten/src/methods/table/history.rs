@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+use std::fs::{ File, OpenOptions };
+use std::io::Write;
+use std::path::{ Path, PathBuf };
+use std::time::SystemTime;
+
+/// Values committed for each key sequence, persisted as an append log of
+/// `sequence<TAB>value<TAB>count` lines which is compacted whenever it is loaded.
+pub struct History {
+	path: PathBuf,
+	counts: HashMap<String, HashMap<String, u32>>, //<Sequence, <Value, Count>>
+	modified: Option<SystemTime>, //of the file once last written or read, see `History::is_stale`
+}
+
+impl History {
+	/// `$XDG_DATA_HOME/benten/history/<table>.db`
+	pub fn path(table_id: &str) -> PathBuf {
+		xdg::BaseDirectories::with_prefix("benten").unwrap().get_data_home().join("history").join(table_id).with_extension("db")
+	}
+
+	pub fn load(table_id: &str) -> Self {
+		let path = Self::path(table_id);
+		let counts = match std::fs::read_to_string(&path) {
+			Ok(data) => parse(&data),
+			Err(_) => HashMap::new(),
+		};
+
+		let mut history = History { path, counts, modified: None };
+		if let Err(e) = std::fs::create_dir_all(history.path.parent().unwrap()).and_then(|_| history.compact()) {
+			log::warn!("could not compact history `{}`: {}", history.path.display(), e);
+		}
+
+		history.modified = modified(&history.path);
+		history
+	}
+
+	/// Whether the file was changed by something else since, such as `benten-cli reset-history` removing it
+	pub fn is_stale(&self) -> bool {
+		modified(&self.path)!=self.modified
+	}
+
+	pub fn count(&self, sequence: &str, value: &str) -> u32 {
+		self.counts.get(sequence).and_then(|values| values.get(value)).copied().unwrap_or(0)
+	}
+
+	/// Counts a commit of `value` for `sequence`, appending it to the log
+	pub fn record(&mut self, sequence: &str, value: &str) {
+		*self.counts.entry(sequence.to_string()).or_default().entry(value.to_string()).or_insert(0) += 1;
+
+		let result = OpenOptions::new().create(true).append(true).open(&self.path)
+			.and_then(|mut file| writeln!(file, "{}\t{}\t1", sequence, value));
+		if let Err(e) = result {
+			log::warn!("could not write history `{}`: {}", self.path.display(), e);
+		}
+
+		self.modified = modified(&self.path);
+	}
+
+	/// Rewrites the log with a single line per sequence and value
+	fn compact(&self) -> std::io::Result<()> {
+		if self.counts.is_empty() {
+			return Ok(());
+		}
+
+		let mut file = File::create(&self.path)?;
+		for (sequence, values) in &self.counts {
+			for (value, count) in values {
+				writeln!(file, "{}\t{}\t{}", sequence, value, count)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+	std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+fn parse(data: &str) -> HashMap<String, HashMap<String, u32>> {
+	let mut counts: HashMap<String, HashMap<String, u32>> = HashMap::new();
+	for line in data.lines() {
+		let mut columns = line.split('\t');
+		if let (Some(sequence), Some(value), Some(Ok(count))) = (columns.next(), columns.next(), columns.next().map(str::parse::<u32>)) {
+			*counts.entry(sequence.to_string()).or_default().entry(value.to_string()).or_insert(0) += count;
+		}
+	}
+
+	counts
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn compacts_log() {
+		let counts = parse("a\t日\t3\na\t曰\t1\na\t日\t1\nbroken line\naa\t日日\t2\n");
+		assert_eq!(counts["a"]["日"], 4);
+		assert_eq!(counts["a"]["曰"], 1);
+		assert_eq!(counts["aa"]["日日"], 2);
+		assert_eq!(counts.len(), 2);
+	}
+}
@@ -1,22 +1,31 @@
+mod cache;
+pub mod history;
 pub mod parser;
+pub mod registry;
 pub use parser::*;
 
+use history::History;
+
+use std::cmp::Reverse;
 use std::collections::HashSet;
+use std::sync::{ Arc, Mutex };
 use crate::methods::GenericMethodTrait;
 use crate::methods::layout::{ LayoutMethodTrait, LayoutHelper, parser::* };
 use std::path::Path;
-use crate::{ BentenError, BentenResponse, Function };
+use crate::{ BentenError, BentenResponse, Function, Width };
 
 pub struct TableMethod {
 	/// Layout variables
 	pub layout: Layout,
 	pub modifiers_pressed: HashSet<u16>, //maybe convert these two into a `LayoutMethod`
+	pub latched: HashSet<u16>,
 
 	/// Table variables
-	pub table: Table,
+	pub table: Arc<Table>, //shared with the other methods using the table, see `registry.rs`
 	pub relative_entries: Vec<Entry>,
 	pub key_sequence: String,
-	pub index: usize
+	pub index: usize,
+	pub history: Option<Arc<Mutex<History>>>, //shared like `table`
 }
 
 impl TryFrom<Table> for TableMethod {
@@ -26,28 +35,13 @@ impl TryFrom<Table> for TableMethod {
         //temporary
         let path = xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home();
 
+        let layout = Layout::from_path(&table.id, &path)?;
         Ok(TableMethod {
-            layout: Layout::from_path(&table.id, &path)?,
-            table,
-            modifiers_pressed: HashSet::new(),
-            relative_entries: Vec::new(),
-            key_sequence: String::with_capacity(5),
-            index: 0
-        })
-    }  
-}
-
-impl TryFrom<Layout> for TableMethod {
-    type Error = BentenError;
-
-    fn try_from(layout: Layout) -> Result<Self, Self::Error> {
-        //temporary
-        let path = xdg::BaseDirectories::with_prefix("benten").unwrap().get_config_home();
-
-        Ok(TableMethod {
-            table: Table::from_path(&layout.id, &path)?,
+            history: history(&layout),
             layout,
+            table: Arc::new(table),
             modifiers_pressed: HashSet::new(),
+            latched: HashSet::new(),
             relative_entries: Vec::new(),
             key_sequence: String::with_capacity(5),
             index: 0
@@ -58,14 +52,7 @@ impl TryFrom<Layout> for TableMethod {
 //feature: copy previous character key bind, kinda like a repition mark, will need a var "previous character" buf in TableMethod
 impl GenericMethodTrait for TableMethod {
     fn new(id: &str, path: &Path) -> Result<Self, BentenError> {
-        Ok(TableMethod {
-            table: Table::from_path(id, &path)?,
-            layout: Layout::from_path(id, &path)?,
-            modifiers_pressed: HashSet::new(),
-            relative_entries: Vec::new(),
-            key_sequence: String::with_capacity(5),
-            index: 0
-        })
+        TableMethod::from_layout(Layout::from_path(id, path)?, path)
     }
 
     fn on_key_press(&mut self, key_code: u16) -> BentenResponse {
@@ -75,77 +62,98 @@ impl GenericMethodTrait for TableMethod {
         }
 
         let mut commit = false;
+    	let mut pushed = false;
     	match self.calculate_special_key(&key_code).as_deref() {
     		Some("COMMIT") => commit = true,
     		Some("BACKSPACE") => { self.key_sequence.pop(); },
-    		Some("NEXT") => self.index = self.index+1,
-    		Some("PREV") => self.index = self.index-1,
+    		Some("WILDCARD") => {
+    			self.key_sequence.push(WILDCARD);
+    			pushed = true;
+    		},
+    		Some("NEXT") => self.index += 1,
+    		Some("PREV") => self.index -= 1,
     		_ => {},
     	}
 
-        let mut changemethodto = String::new();
-        let key_sequence_empty = self.key_sequence.is_empty();
-        if let Some(bindings) = &self.layout().bindings {
-            if let Some(functions) = bindings.get(&key_code) {
-                for function in functions {
-                    match function {
-                        Some(Function::ChangeMethodTo(m)) => changemethodto = m.to_string(),
-                        Some(Function::IfEmptyChangeMethodTo(m)) => {
-                            if key_sequence_empty {
-                                changemethodto = m.to_string()
-                            } 
-                        }, 
-                        _ => {},
-                    }
-                }
-            }
-        }
-
     	if let Some(c) = self.calculate_char(&key_code) {
     		self.key_sequence.push_str(&c);
+    		pushed = true;
     	}
 
-
-        //change method when key sequence is empty on backspace
-        if !changemethodto.is_empty() && !commit {
-           return BentenResponse::Function(Function::ChangeMethodTo(changemethodto))
-        }
-
-        if let Some(value) = self.calculate_char_dict() {
-            if commit {
-                self.reset();
-                
-                if !changemethodto.is_empty() {
-                   return BentenResponse::Function(Function::CommitThenChangeMethodTo(value, changemethodto))
+        let value = self.calculate_char_dict();
+        let commit = commit || (pushed && value.is_some() && self.should_auto_commit());
+        match value {
+            Some(value) if commit => {
+                if let Some(history) = &self.history {
+                    history.lock().unwrap().record(&self.key_sequence, &value);
                 }
-
-                return BentenResponse::Commit(value)
-            } else {
-                return BentenResponse::Suggest(value)
+                self.reset();
+                BentenResponse::Commit(value)
+            },
+            //show which code the candidate has when it was not fully typed
+            Some(value) if self.key_sequence.contains(WILDCARD) => {
+                BentenResponse::Hint(value, self.relative_entries[self.index].sequence.clone())
+            },
+            Some(value) => BentenResponse::Suggest(value),
+            //no candidate yet, but longer sequences have some
+            None if !self.key_sequence.is_empty() && self.table.count_prefixed(&self.key_sequence, 1)>0 => {
+                BentenResponse::Suggest(self.key_sequence.clone())
+            },
+            None => {
+                self.reset();
+                BentenResponse::Empty
             }
-        } else {
-            self.reset();
-            return BentenResponse::Empty
         }
     }
 
     fn on_key_release(&mut self, key_code: u16) -> BentenResponse {
         if let Some(modifier) = self.is_get_modifier(&key_code) {
             self.on_modifier_release(&modifier, &key_code);
+        } else {
+            self.release_latches();
         }
 
-        return BentenResponse::Undefined
+        BentenResponse::Undefined
     }
 
     fn id(&self) -> &str {
         &self.layout.id
     }
 
+    fn label(&self) -> &str {
+        self.layout.label.as_deref().unwrap_or(&self.layout.id)
+    }
+
+    fn width(&self) -> Option<Width> {
+        self.layout.width
+    }
+
+    fn bindings(&self, key_code: u16) -> Vec<Function> {
+        self.layout.bindings_of(key_code)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.key_sequence.is_empty()
+    }
+
+    fn candidates(&self) -> Vec<(String, String)> {
+        self.relative_entries.iter().map(|entry| (entry.value.clone(), entry.sequence.clone())).collect()
+    }
+
+    fn reverse_lookup(&self, value: &str) -> Vec<String> {
+        self.table.reverse_lookup(value)
+    }
+
     fn reset(&mut self) {
         self.index = 0;
         self.relative_entries.clear();
         self.key_sequence.clear();
+    }
+
+    fn clear(&mut self) {
+        self.reset();
         self.modifiers_pressed.clear();
+        self.latched.clear();
     }
 }
 
@@ -157,6 +165,10 @@ impl LayoutHelper for TableMethod {
     fn modifiers_pressed(&mut self) -> &mut HashSet<u16> {
         &mut self.modifiers_pressed
     }
+
+    fn latched(&mut self) -> &mut HashSet<u16> {
+        &mut self.latched
+    }
         
     fn layout_n_modifiers_pressed(&mut self) -> (&Layout, &mut HashSet<u16>) {
         (&self.layout, &mut self.modifiers_pressed)
@@ -166,34 +178,115 @@ impl LayoutHelper for TableMethod {
 impl LayoutMethodTrait for TableMethod {}
 
 impl TableMethod {
+    /// Method of `layout`, whose table is read from the `tables` of `base_dir`
+    pub fn from_layout(layout: Layout, base_dir: &Path) -> Result<Self, BentenError> {
+        Ok(TableMethod {
+            table: registry::get(&layout.id, base_dir)?,
+            history: history(&layout),
+            layout,
+            modifiers_pressed: HashSet::new(),
+            latched: HashSet::new(),
+            relative_entries: Vec::new(),
+            key_sequence: String::with_capacity(5),
+            index: 0
+        })
+    }
+
     pub fn calculate_char_dict(&mut self) -> Option<String> {
+        if self.key_sequence.is_empty() {
+            return None;
+        }
+
+        self.relative_entries = self.table.lookup(&self.key_sequence, self.options().lookup);
+        let history = self.history.as_ref().map(|history| history.lock().unwrap());
+        order_candidates(&mut self.relative_entries, &self.key_sequence, history.as_deref());
+
         //Tolerate index
-        if !self.key_sequence.is_empty() {
-            if self.index>=self.relative_entries.len() {
-                self.index = 0
-            }
+        if self.index>=self.relative_entries.len() {
+            self.index = 0
+        }
 
-            // let key_sequence: &str = &self.key_sequence;
+        //Get candidate
+        self.relative_entries.get(self.index).map(|entry| entry.value.to_owned())
+    }
 
-            //If relative entries is not yet made, make it
-            // if key_sequence.len()==1 { 
-            self.relative_entries.clear();
-            for entry in &self.table.entries {
-                if entry.sequence.starts_with(&self.key_sequence) {
-                    self.relative_entries.push(entry.clone());
-                }
-            }
-      //    } else {
-      //        //Filter by remove non-matching entries from relative_entries
-      //        self.relative_entries.retain(|entry| entry.sequence.starts_with(&*key_sequence));
-            // }
-
-            //Get candidate
-            if let Some(entry) = self.relative_entries.get(self.index).map(|x| x.to_owned()) {
-                return Some(entry.character.to_string());
-            }
+    fn options(&self) -> TableOptions {
+        self.layout.table.clone().unwrap_or_default()
+    }
+
+    /// Whether the key sequence is complete, either by its length or by leading to a single entry
+    fn should_auto_commit(&self) -> bool {
+        let options = self.options();
+        if options.max_length.is_some_and(|max_length| self.key_sequence.chars().count()>=max_length) {
+            return true;
         }
 
-        None
+        options.auto_commit && self.table.count_prefixed(&self.key_sequence, 2)==1
+    }
+}
+
+/// Exact matches of the key sequence first, then by how often they were committed for it, then
+/// by descending weight and then shorter values, so characters come before phrases, keeping file
+/// order otherwise
+fn order_candidates(entries: &mut [Entry], key_sequence: &str, history: Option<&History>) {
+    entries.sort_by_key(|entry| (
+        !matches(&entry.sequence, key_sequence, false),
+        Reverse(history.map_or(0, |history| history.count(key_sequence, &entry.value))),
+        Reverse(entry.weight.unwrap_or(0)),
+        entry.value.chars().count(),
+    ));
+}
+
+/// Learned history of the table, when enabled in the layout
+fn history(layout: &Layout) -> Option<Arc<Mutex<History>>> {
+    match &layout.table {
+        Some(options) if options.learn => Some(registry::history(&layout.id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(value: &str, sequence: &str, weight: Option<u32>) -> Entry {
+        Entry { value: value.to_string(), sequence: sequence.to_string(), weight }
     }
-}
\ No newline at end of file
+
+    fn values(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.value.as_str()).collect()
+    }
+
+    #[test]
+    fn exact_match_then_weight() {
+        let mut entries = vec![entry("昌", "aa", Some(90)), entry("曰", "a", Some(10)), entry("日", "a", Some(50)), entry("明", "ab", None)];
+        order_candidates(&mut entries, "a", None);
+        assert_eq!(values(&entries), ["日", "曰", "昌", "明"]);
+    }
+
+    #[test]
+    fn characters_before_phrases() {
+        let mut entries = vec![entry("日日", "aa", None), entry("昌", "aa", None), entry("日本", "aa", Some(5))];
+        order_candidates(&mut entries, "aa", None);
+        assert_eq!(values(&entries), ["日本", "昌", "日日"]);
+    }
+
+    #[test]
+    fn lookup_policies() {
+        let table = Table::new("test", vec![entry("日", "a", None), entry("昌", "aa", None), entry("明", "ab", None)]);
+        assert_eq!(values(&table.lookup("a", Lookup::Exact)), ["日"]);
+        assert_eq!(values(&table.lookup("a", Lookup::Prefix)), ["日", "昌", "明"]);
+        assert_eq!(values(&table.lookup("a", Lookup::ExactThenPrefix)), ["日"]);
+        assert_eq!(values(&table.lookup("", Lookup::Exact)), Vec::<&str>::new());
+        assert_eq!(table.count_prefixed("aa", 2), 1);
+        assert_eq!(table.reverse_lookup("明"), ["ab"]);
+    }
+
+    #[test]
+    fn wildcards() {
+        let table = Table::new("test", vec![entry("明", "ab", None), entry("日", "a", None), entry("昌", "aa", None), entry("間", "anaa", None)]);
+        assert_eq!(values(&table.lookup("a?", Lookup::Exact)), ["明", "昌"]);
+        assert_eq!(values(&table.lookup("?a", Lookup::Prefix)), ["昌"]);
+        assert_eq!(values(&table.lookup("a??a", Lookup::Exact)), ["間"]);
+    }
+}
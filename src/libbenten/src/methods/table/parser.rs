@@ -4,29 +4,137 @@ use std::io::BufReader;
 use std::path::Path;
 
 use crate::BentenError;
+use super::cache::{ self, Entries };
 
-#[derive(Debug, PartialEq, Deserialize)]
+/// Stands for any single key in a key sequence, typed with the `WILDCARD` special key
+pub const WILDCARD: char = '?';
+
+#[derive(Debug, PartialEq)]
 pub struct Table {
 	pub id: String,
-	pub entries: Vec<Entry>
+	pub entries: Entries,
 }
 
 #[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Entry {
-	pub character: char,
+	#[serde(alias = "character")]
+	pub value: String, //a single character or a phrase
 	pub sequence: String, //maybe try a tiny_string as this is needlessly large
+	#[serde(default)]
+	pub weight: Option<u32>, //higher comes first among candidates of the same sequence
+}
+
+/// Options of table methods, set with `table` in the layout
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct TableOptions {
+	/// Promote the values committed most often for a sequence, see `history.rs`
+	#[serde(default)]
+	pub learn: bool,
+	#[serde(default)]
+	pub lookup: Lookup,
+	/// Commit as soon as a single entry starts with the key sequence
+	#[serde(default)]
+	pub auto_commit: bool,
+	/// Commit once the key sequence is this many keys long
+	#[serde(default)]
+	pub max_length: Option<usize>,
+}
+
+/// Which entries are candidates for a key sequence
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+pub enum Lookup {
+	/// Entries whose sequence is the key sequence
+	Exact,
+	/// Entries whose sequence is the key sequence, or starts with it when there are none
+	ExactThenPrefix,
+	/// Entries whose sequence starts with the key sequence
+	#[default]
+	Prefix,
 }
 
 impl Table {
+	pub fn new(id: &str, entries: Vec<Entry>) -> Self {
+		Self {
+			id: id.to_string(),
+			entries: Entries::new(&entries),
+		}
+	}
+
+	/// Reads `tables/<id>.dict`, or its compiled form when it is cached and up to date, see `cache.rs`
 	pub fn from_path(id: &str, base_dir: &Path) -> Result<Table, BentenError> {
 		let path = base_dir.join("tables").join(id).with_extension("dict");
-		let file = File::open(path)?;
+		if let Some(table) = cache::load(id, &path) {
+			return Ok(table);
+		}
+
+		let file = File::open(&path)?;
 	    let reader = BufReader::new(file);
 	    let entries = csv::Reader::from_reader(reader).deserialize().collect::<Result<Vec<_>, _>>()?;
 
-		Ok(Self {
-			id: id.to_string(),
-			entries,
-		})
+		let table = Table::new(id, entries);
+		if let Err(e) = cache::store(&table, &path) {
+			log::warn!("could not cache table `{}`: {}", id, e);
+		}
+
+		Ok(table)
+	}
+
+	/// Candidates for `key_sequence`, in the order of the table
+	pub fn lookup(&self, key_sequence: &str, lookup: Lookup) -> Vec<Entry> {
+		let find = |prefix: bool| {
+			let mut indexes: Vec<usize> = self.prefixed(key_sequence).filter(|i| matches(self.entries.sequence(*i), key_sequence, prefix)).collect();
+			indexes.sort_unstable();
+			indexes.into_iter().map(|i| self.entries.get(i)).collect::<Vec<_>>()
+		};
+
+		match lookup {
+			Lookup::Exact => find(false),
+			Lookup::ExactThenPrefix => {
+				let entries = find(false);
+				if entries.is_empty() { find(true) } else { entries }
+			},
+			Lookup::Prefix => find(true),
+		}
 	}
+
+	/// Every key sequence producing `value`
+	pub fn reverse_lookup(&self, value: &str) -> Vec<String> {
+		(0..self.entries.len()).filter(|i| self.entries.value(*i)==value).map(|i| self.entries.sequence(i).to_string()).collect()
+	}
+
+	/// Number of entries starting with `key_sequence`, counting no further than `max`
+	pub fn count_prefixed(&self, key_sequence: &str, max: usize) -> usize {
+		self.prefixed(key_sequence).filter(|i| matches(self.entries.sequence(*i), key_sequence, true)).take(max).count()
+	}
+
+	/// Indexes of the entries starting with `key_sequence` up to its first wildcard, by sequence
+	fn prefixed<'a>(&'a self, key_sequence: &'a str) -> impl Iterator<Item = usize> + 'a {
+		let prefix = key_sequence.split(WILDCARD).next().unwrap_or_default();
+		let (mut start, mut end) = (0, self.entries.len());
+		while start<end {
+			let middle = (start+end)/2;
+			if self.entries.sequence(self.entries.sorted(middle))<prefix { start = middle+1 } else { end = middle }
+		}
+
+		(start..self.entries.len())
+			.map(|i| self.entries.sorted(i))
+			.take_while(move |i| self.entries.sequence(*i).starts_with(prefix))
+	}
+}
+
+/// Whether `sequence` is, or with `prefix` starts with, `key_sequence`, a `WILDCARD` matching any key
+pub fn matches(sequence: &str, key_sequence: &str, prefix: bool) -> bool {
+	if !key_sequence.contains(WILDCARD) {
+		return if prefix { sequence.starts_with(key_sequence) } else { sequence==key_sequence };
+	}
+
+	let mut sequence = sequence.chars();
+	for key in key_sequence.chars() {
+		match sequence.next() {
+			Some(c) if key==WILDCARD || key==c => {},
+			_ => return false,
+		}
+	}
+
+	prefix || sequence.next().is_none()
 }
\ No newline at end of file
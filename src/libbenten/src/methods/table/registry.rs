@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::sync::{ Arc, Mutex, OnceLock, Weak };
+use std::time::SystemTime;
+
+use crate::BentenError;
+use super::Table;
+use super::history::History;
+
+/// Tables currently in use by a method, by the path they were read from, so that methods and
+/// layouts using the same table share a single copy.
+static TABLES: OnceLock<Mutex<HashMap<PathBuf, Loaded>>> = OnceLock::new();
+
+/// Histories of the tables currently in use by a method, by table, so that methods learning from
+/// the same table record into and order by the same counts.
+static HISTORIES: OnceLock<Mutex<HashMap<String, Weak<Mutex<History>>>>> = OnceLock::new();
+
+struct Loaded {
+	table: Weak<Table>,
+	modified: Option<SystemTime>, //of the file when it was read
+}
+
+/// Table `id` of `base_dir`, read again only when no method holds it anymore or it was modified since
+pub fn get(id: &str, base_dir: &Path) -> Result<Arc<Table>, BentenError> {
+	let path = base_dir.join("tables").join(id).with_extension("dict");
+	let modified = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+
+	let mut tables = TABLES.get_or_init(Default::default).lock().unwrap();
+	if let Some(loaded) = tables.get(&path) {
+		if let Some(table) = loaded.table.upgrade().filter(|_| loaded.modified==modified) {
+			return Ok(table);
+		}
+	}
+
+	let table = Arc::new(Table::from_path(id, base_dir)?);
+	tables.retain(|_, loaded| loaded.table.strong_count()>0);
+	tables.insert(path, Loaded { table: Arc::downgrade(&table), modified });
+
+	Ok(table)
+}
+
+/// History of table `id`, loaded again only when no method holds it anymore or its file was
+/// changed by something else, as when it is reset
+pub fn history(id: &str) -> Arc<Mutex<History>> {
+	let mut histories = HISTORIES.get_or_init(Default::default).lock().unwrap();
+	if let Some(history) = histories.get(id).and_then(Weak::upgrade) {
+		if !history.lock().unwrap().is_stale() {
+			return history;
+		}
+	}
+
+	let history = Arc::new(Mutex::new(History::load(id)));
+	histories.retain(|_, history| history.strong_count()>0);
+	histories.insert(id.to_string(), Arc::downgrade(&history));
+
+	history
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Directory holding `tables/<id>.dict` with `entries`
+	fn base_dir(id: &str, entries: &str) -> PathBuf {
+		crate::test_dirs();
+		let base_dir = std::env::temp_dir().join("benten-registry").join(id);
+		std::fs::create_dir_all(base_dir.join("tables")).unwrap();
+		std::fs::write(base_dir.join("tables").join(id).with_extension("dict"), format!("character,sequence\n{}", entries)).unwrap();
+		base_dir
+	}
+
+	#[test]
+	fn shared_between_methods() {
+		let base_dir = base_dir("registry-shared", "日,a\n");
+		let table = get("registry-shared", &base_dir).unwrap();
+		let other = get("registry-shared", &base_dir).unwrap();
+		assert!(Arc::ptr_eq(&table, &other));
+	}
+
+	#[test]
+	fn read_again_once_dropped_or_modified() {
+		let base_dir = base_dir("registry-reload", "日,a\n");
+		let table = get("registry-reload", &base_dir).unwrap();
+		let dropped = Arc::downgrade(&table);
+		drop(table);
+		assert!(dropped.upgrade().is_none());
+
+		let table = get("registry-reload", &base_dir).unwrap();
+		assert_eq!(table.reverse_lookup("日"), vec!["a"]);
+
+		// held by a method, but modified since
+		self::base_dir("registry-reload", "日,a\n明,ab\n");
+		let modified = get("registry-reload", &base_dir).unwrap();
+		assert!(!Arc::ptr_eq(&table, &modified));
+		assert_eq!(modified.reverse_lookup("明"), vec!["ab"]);
+	}
+
+	#[test]
+	fn history_read_again_once_reset() {
+		crate::test_dirs();
+		let history = self::history("registry-history");
+		history.lock().unwrap().record("a", "日");
+		assert!(Arc::ptr_eq(&history, &self::history("registry-history")));
+
+		// as `benten-cli reset-history` does
+		std::fs::remove_file(History::path("registry-history")).unwrap();
+		let reset = self::history("registry-history");
+		assert!(!Arc::ptr_eq(&history, &reset));
+		assert_eq!(reset.lock().unwrap().count("a", "日"), 0);
+	}
+}
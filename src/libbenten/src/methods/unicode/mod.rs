@@ -0,0 +1,225 @@
+pub mod names;
+
+use std::collections::HashSet;
+use crate::methods::GenericMethodTrait;
+use crate::methods::layout::{ LayoutMethodTrait, LayoutHelper, parser::* };
+use std::path::Path;
+use crate::{ BentenError, BentenResponse, Function, Width };
+
+/// Input of characters by their hexadecimal code point, or by their name when toggled with `NAME`.
+/// Several characters can be entered at once by separating them with `SEPARATOR` or whitespace.
+pub struct UnicodeMethod {
+    /// Layout variables
+    pub layout: Layout,
+    pub modifiers_pressed: HashSet<u16>,
+    pub latched: HashSet<u16>,
+
+    /// Unicode variables
+    pub characters: String,     //characters already entered
+    pub buffer: String,         //hex digits, or name query when `by_name`
+    pub by_name: bool,
+    pub candidates: Vec<char>,
+    pub index: usize
+}
+
+impl From<Layout> for UnicodeMethod {
+    fn from(layout: Layout) -> Self {
+        UnicodeMethod {
+            layout,
+            modifiers_pressed: HashSet::new(),
+            latched: HashSet::new(),
+            characters: String::new(),
+            buffer: String::with_capacity(6),
+            by_name: false,
+            candidates: Vec::new(),
+            index: 0
+        }
+    }
+}
+
+impl GenericMethodTrait for UnicodeMethod {
+    fn new(id: &str, path: &Path) -> Result<Self, BentenError> {
+        Ok(UnicodeMethod::from(Layout::from_path(id, path)?))
+    }
+
+    fn on_key_press(&mut self, key_code: u16) -> BentenResponse {
+        if let Some(modifier) = self.is_get_modifier(&key_code) {
+            self.on_modifier_press(&modifier, &key_code);
+            return BentenResponse::Undefined;
+        }
+
+        let mut commit = false;
+        match self.calculate_special_key(&key_code).as_deref() {
+            Some("COMMIT") => commit = true,
+            Some("BACKSPACE") => self.backspace(),
+            Some("SEPARATOR") => self.separate(),
+            Some("NAME") => {
+                self.separate();
+                self.by_name = !self.by_name;
+            },
+            Some("NEXT") => self.index += 1,
+            Some("PREV") => self.index = self.index.saturating_sub(1),
+            _ => {},
+        }
+
+        if let Some(c) = self.calculate_char(&key_code) {
+            self.push_str(&c);
+        }
+
+        if commit {
+            self.separate();
+            let value = std::mem::take(&mut self.characters);
+            self.reset();
+
+            if value.is_empty() {
+                return BentenResponse::Empty
+            }
+
+            return BentenResponse::Commit(value)
+        }
+
+        match self.preview() {
+            Some(preview) => BentenResponse::Suggest(preview),
+            None => BentenResponse::Empty
+        }
+    }
+
+    fn on_key_release(&mut self, key_code: u16) -> BentenResponse {
+        if let Some(modifier) = self.is_get_modifier(&key_code) {
+            self.on_modifier_release(&modifier, &key_code);
+        } else {
+            self.release_latches();
+        }
+
+        BentenResponse::Undefined
+    }
+
+    fn id(&self) -> &str {
+        &self.layout.id
+    }
+
+    fn label(&self) -> &str {
+        self.layout.label.as_deref().unwrap_or(&self.layout.id)
+    }
+
+    fn width(&self) -> Option<Width> {
+        self.layout.width
+    }
+
+    fn bindings(&self, key_code: u16) -> Vec<Function> {
+        self.layout.bindings_of(key_code)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.characters.is_empty() && self.buffer.is_empty()
+    }
+
+    fn reset(&mut self) {
+        self.characters.clear();
+        self.buffer.clear();
+        self.by_name = false;
+        self.candidates.clear();
+        self.index = 0;
+    }
+
+    fn clear(&mut self) {
+        self.reset();
+        self.modifiers_pressed.clear();
+        self.latched.clear();
+    }
+}
+
+impl LayoutHelper for UnicodeMethod {
+    fn layout(&mut self) -> &Layout {
+        &self.layout
+    }
+
+    fn modifiers_pressed(&mut self) -> &mut HashSet<u16> {
+        &mut self.modifiers_pressed
+    }
+
+    fn latched(&mut self) -> &mut HashSet<u16> {
+        &mut self.latched
+    }
+
+    fn layout_n_modifiers_pressed(&mut self) -> (&Layout, &mut HashSet<u16>) {
+        (&self.layout, &mut self.modifiers_pressed)
+    }
+}
+
+impl LayoutMethodTrait for UnicodeMethod {}
+
+impl UnicodeMethod {
+    fn push_str(&mut self, value: &str) {
+        for c in value.chars() {
+            if self.by_name {
+                self.buffer.push(c);
+            } else if c.is_whitespace() {
+                self.separate();
+            } else if c.is_ascii_hexdigit() && self.buffer.len()<6 {
+                self.buffer.push(c);
+            }
+        }
+
+        self.update_candidates();
+    }
+
+    fn backspace(&mut self) {
+        if self.buffer.pop().is_none() {
+            self.characters.pop();
+        }
+
+        self.update_candidates();
+    }
+
+    /// Finishes the character currently being entered
+    fn separate(&mut self) {
+        if let Some(c) = self.current() {
+            self.characters.push(c);
+        }
+
+        self.buffer.clear();
+        self.candidates.clear();
+        self.index = 0;
+    }
+
+    fn update_candidates(&mut self) {
+        self.index = 0;
+        self.candidates = if self.by_name {
+            names::search(&self.buffer)
+        } else {
+            Vec::new()
+        };
+    }
+
+    /// Character described by the buffer, if any
+    fn current(&mut self) -> Option<char> {
+        if self.by_name {
+            //Tolerate index
+            if self.index>=self.candidates.len() {
+                self.index = 0
+            }
+
+            self.candidates.get(self.index).copied()
+        } else {
+            u32::from_str_radix(&self.buffer, 16).ok().and_then(char::from_u32)
+        }
+    }
+
+    /// Text shown while typing, falls back to the raw buffer when it does not describe a character yet
+    fn preview(&mut self) -> Option<String> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut preview = self.characters.clone();
+        match self.current() {
+            Some(c) => preview.push(c),
+            None if self.by_name => preview.push_str(&self.buffer),
+            None if !self.buffer.is_empty() => preview.push_str(&format!("U+{}", self.buffer.to_uppercase())),
+            None => {}
+        }
+
+        Some(preview)
+    }
+}
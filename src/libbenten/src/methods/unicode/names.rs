@@ -0,0 +1,78 @@
+use std::sync::OnceLock;
+
+/// Bundled `<CodePoint>;<Name>` table, see the header of the file for what is omitted
+static NAMES: &str = include_str!("../../../data/names.txt");
+
+/// Characters of `NAMES` along with every word of their names, sorted, so that searching takes
+/// the names with a word starting with the query instead of going through all of them
+struct Index {
+    names: Vec<(&'static str, char)>,
+    words: Vec<(&'static str, u32)>, //<Word, Index in `names`>
+}
+
+static INDEX: OnceLock<Index> = OnceLock::new();
+
+impl Index {
+    fn new() -> Self {
+        let mut names = Vec::new();
+        let mut words = Vec::new();
+        for line in NAMES.lines().filter(|line| !line.starts_with('#')) {
+            if let Some((code_point, name)) = line.split_once(';') {
+                if let Some(c) = u32::from_str_radix(code_point, 16).ok().and_then(char::from_u32) {
+                    words.extend(split_words(name).map(|word| (word, names.len() as u32)));
+                    names.push((name, c));
+                }
+            }
+        }
+
+        words.sort_unstable();
+        Index { names, words }
+    }
+
+    /// Entries of `words` starting with `prefix`
+    fn prefixed(&self, prefix: &str) -> &[(&'static str, u32)] {
+        let start = self.words.partition_point(|(word, _)| *word<prefix);
+        let len = self.words[start..].partition_point(|(word, _)| word.starts_with(prefix));
+        &self.words[start..start+len]
+    }
+}
+
+/// Every character whose name contains each word of `query` as the start of one of its words,
+/// ordered by exact matches first and then by the length of the name.
+pub fn search(query: &str) -> Vec<char> {
+    let query = query.to_uppercase();
+    let words: Vec<&str> = split_words(&query).collect();
+    let index = INDEX.get_or_init(Index::new);
+
+    // the names having the word with the fewest matches, left to check for the other words
+    let mut found: Vec<u32> = match words.iter().map(|word| index.prefixed(word)).min_by_key(|found| found.len()) {
+        Some(found) => found.iter().map(|(_, i)| *i).collect(),
+        None => return Vec::new(),
+    };
+    found.sort_unstable();
+    found.dedup();
+
+    let mut matches: Vec<(&str, char)> = found.into_iter()
+        .map(|i| index.names[i as usize])
+        .filter(|(name, _)| words.iter().all(|word| split_words(name).any(|w| w.starts_with(word))))
+        .collect();
+
+    matches.sort_by_key(|(name, _)| (!split_words(name).eq(words.iter().copied()), name.len()));
+    matches.into_iter().map(|(_, c)| c).collect()
+}
+
+fn split_words(name: &str) -> impl Iterator<Item = &str> {
+    name.split(|c: char| c.is_whitespace() || c=='-').filter(|word| !word.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn search_by_words() {
+        assert_eq!(search("hiragana letter small ka").first(), Some(&'ゕ'));
+        assert_eq!(search("latin small letter a").first(), Some(&'a'));
+        assert!(search("").is_empty());
+    }
+}
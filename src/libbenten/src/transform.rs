@@ -0,0 +1,167 @@
+use serde::Deserialize;
+
+/// Offset between a hiragana and the katakana of the same sound
+const KANA_OFFSET: u32 = 0x60;
+/// Offset between a printable ASCII character and its fullwidth form
+const WIDTH_OFFSET: u32 = 0xFEE0;
+const IDEOGRAPHIC_SPACE: char = '\u{3000}';
+
+/// Halfwidth katakana and punctuation, each along with its fullwidth form in `FULLWIDTH_KANA`
+const HALFWIDTH_KANA: &str = "｡｢｣､･ｦｧｨｩｪｫｬｭｮｯｰｱｲｳｴｵｶｷｸｹｺｻｼｽｾｿﾀﾁﾂﾃﾄﾅﾆﾇﾈﾉﾊﾋﾌﾍﾎﾏﾐﾑﾒﾓﾔﾕﾖﾗﾘﾙﾚﾛﾜﾝﾞﾟ";
+const FULLWIDTH_KANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+/// Katakana written in halfwidth with a separate mark, each coming right after the katakana without it
+const VOICED: &str = "ガギグゲゴザジズゼゾダヂヅデドバビブベボ";
+const SEMI_VOICED: &str = "パピプペポ";
+const HALFWIDTH_VOICED_MARK: char = 'ﾞ';
+const HALFWIDTH_SEMI_VOICED_MARK: char = 'ﾟ';
+
+/// Conversions a modifier can apply to the values of a layout, instead of them being written out
+/// at another level
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Transform {
+    Katakana,
+    Hiragana,
+    Fullwidth,
+    Halfwidth,
+    Uppercase,
+    Lowercase,
+}
+
+impl Transform {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Transform::Katakana => text.chars().map(to_katakana).collect(),
+            Transform::Hiragana => text.chars().map(to_hiragana).collect(),
+            Transform::Fullwidth => to_fullwidth(text),
+            Transform::Halfwidth => to_halfwidth(text),
+            Transform::Uppercase => text.to_uppercase(),
+            Transform::Lowercase => text.to_lowercase(),
+        }
+    }
+}
+
+fn to_katakana(c: char) -> char {
+    match c {
+        'ぁ'..='ゖ' | 'ゝ' | 'ゞ' => char::from_u32(c as u32 + KANA_OFFSET).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn to_hiragana(c: char) -> char {
+    match c {
+        'ァ'..='ヶ' | 'ヽ' | 'ヾ' => char::from_u32(c as u32 - KANA_OFFSET).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Width the output of a method is converted to, see `Function::ToggleWidth`
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum Width {
+    Halfwidth,
+    Fullwidth,
+}
+
+impl Width {
+    pub fn apply(self, text: &str) -> String {
+        match self {
+            Width::Halfwidth => to_halfwidth(text),
+            Width::Fullwidth => to_fullwidth(text),
+        }
+    }
+}
+
+fn to_fullwidth(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' => out.push(IDEOGRAPHIC_SPACE),
+            '!'..='~' => out.push(char::from_u32(c as u32 + WIDTH_OFFSET).unwrap_or(c)),
+            _ => match convert(c, HALFWIDTH_KANA, FULLWIDTH_KANA) {
+                Some(kana) => {
+                    // the mark following a halfwidth katakana is part of it
+                    match chars.peek().and_then(|mark| with_mark(kana, *mark)) {
+                        Some(kana) => {
+                            out.push(kana);
+                            chars.next();
+                        },
+                        None => out.push(kana),
+                    }
+                },
+                None => out.push(c),
+            },
+        }
+    }
+
+    out
+}
+
+fn to_halfwidth(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            IDEOGRAPHIC_SPACE => out.push(' '),
+            '！'..='～' => out.push(char::from_u32(c as u32 - WIDTH_OFFSET).unwrap_or(c)),
+            '\u{3099}' => out.push(HALFWIDTH_VOICED_MARK),
+            '\u{309A}' => out.push(HALFWIDTH_SEMI_VOICED_MARK),
+            _ => match without_mark(c) {
+                Some((kana, mark)) => {
+                    out.extend(convert(kana, FULLWIDTH_KANA, HALFWIDTH_KANA));
+                    out.push(mark);
+                },
+                None => out.push(convert(c, FULLWIDTH_KANA, HALFWIDTH_KANA).unwrap_or(c)),
+            },
+        }
+    }
+
+    out
+}
+
+/// Character of `to` at the position of `c` in `from`
+fn convert(c: char, from: &str, to: &str) -> Option<char> {
+    from.chars().zip(to.chars()).find(|(from, _)| *from==c).map(|(_, to)| to)
+}
+
+/// Fullwidth katakana written as `kana` followed by the halfwidth `mark`
+fn with_mark(kana: char, mark: char) -> Option<char> {
+    let (marks, offset) = match mark {
+        HALFWIDTH_VOICED_MARK if kana=='ウ' => return Some('ヴ'),
+        HALFWIDTH_VOICED_MARK => (VOICED, 1),
+        HALFWIDTH_SEMI_VOICED_MARK => (SEMI_VOICED, 2),
+        _ => return None,
+    };
+
+    char::from_u32(kana as u32 + offset).filter(|c| marks.contains(*c))
+}
+
+/// Fullwidth katakana `c` split into the katakana without its mark and the halfwidth mark
+fn without_mark(c: char) -> Option<(char, char)> {
+    let (mark, offset) = match c {
+        'ヴ' => return Some(('ウ', HALFWIDTH_VOICED_MARK)),
+        c if VOICED.contains(c) => (HALFWIDTH_VOICED_MARK, 1),
+        c if SEMI_VOICED.contains(c) => (HALFWIDTH_SEMI_VOICED_MARK, 2),
+        _ => return None,
+    };
+
+    Some((char::from_u32(c as u32 - offset)?, mark))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transforms() {
+        assert_eq!(Transform::Katakana.apply("ゔぁゝー"), "ヴァヽー");
+        assert_eq!(Transform::Hiragana.apply("ヴァヽヷ"), "ゔぁゝヷ");
+        assert_eq!(Transform::Fullwidth.apply("a1 ~"), "ａ１　～");
+        assert_eq!(Transform::Halfwidth.apply("ａ１　～あ"), "a1 ~あ");
+        assert_eq!(Transform::Uppercase.apply("ß"), "SS");
+    }
+
+    #[test]
+    fn halfwidth_katakana() {
+        assert_eq!(Width::Halfwidth.apply("ヴァイオリン、ガッパ。"), "ｳﾞｧｲｵﾘﾝ､ｶﾞｯﾊﾟ｡");
+        assert_eq!(Width::Fullwidth.apply("ｳﾞｧｲｵﾘﾝ､ｶﾞｯﾊﾟ｡ｱﾟﾞ"), "ヴァイオリン、ガッパ。ア゜゛");
+    }
+}
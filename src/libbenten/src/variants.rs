@@ -0,0 +1,113 @@
+use std::collections::HashMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// Bundled standardized variants, in the format of `StandardizedVariants.txt`
+static STANDARDIZED_VARIANTS: &str = include_str!("../data/variants.txt");
+
+/// Registered variation selectors of each base character
+#[derive(Default)]
+pub struct Variants {
+    selectors: HashMap<char, Vec<char>>,
+}
+
+impl Variants {
+    /// Loads the bundled sequences along with every `*.txt` file in `base_dir/variants`, which can
+    /// hold further sequences such as the `IVD_Sequences.txt` published by the Unicode Consortium.
+    pub fn load(base_dir: &Path) -> Self {
+        let mut variants = Variants::default();
+        variants.parse(STANDARDIZED_VARIANTS);
+
+        if let Ok(paths) = std::fs::read_dir(base_dir.join("variants")) {
+            for path in paths.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+                if path.extension().is_some_and(|ext| ext=="txt") {
+                    match read_to_string(&path) {
+                        Ok(data) => variants.parse(&data),
+                        Err(e) => log::warn!("could not read variation sequences `{}`: {}", path.display(), e),
+                    }
+                }
+            }
+        }
+
+        for selectors in variants.selectors.values_mut() {
+            selectors.sort_unstable();
+            selectors.dedup();
+        }
+
+        variants
+    }
+
+    /// Parses lines of `<Base> <Selector>; ...`, which both the standardized variants and the
+    /// ideographic variation database share.
+    fn parse(&mut self, data: &str) {
+        for line in data.lines().filter(|line| !line.starts_with('#')) {
+            let sequence = line.split(';').next().unwrap_or_default();
+            let code_points: Vec<char> = sequence.split_whitespace()
+                .filter_map(|code_point| u32::from_str_radix(code_point, 16).ok())
+                .filter_map(char::from_u32)
+                .collect();
+
+            if let [base, selector] = code_points[..] {
+                self.selectors.entry(base).or_default().push(selector);
+            }
+        }
+    }
+
+    pub fn get(&self, base: char) -> &[char] {
+        self.selectors.get(&base).map_or(&[], |selectors| selectors)
+    }
+}
+
+/// Whether `c` is one of the variation selectors, `U+FE00..U+FE0F` or `U+E0100..U+E01EF`
+pub fn is_variation_selector(c: char) -> bool {
+    matches!(c, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Variation selector chosen for the last committed or suggested text
+pub struct Variant {
+    pub text: String,
+    pub selector: Option<char>,
+    pub committed: bool,
+}
+
+impl Variant {
+    pub fn new(text: String, committed: bool) -> Self {
+        Variant { text, selector: None, committed }
+    }
+
+    /// Moves to the next selector registered for the last character, going back to none after the last one
+    pub fn cycle(&mut self, variants: &Variants) {
+        let selectors = match self.text.chars().last() {
+            Some(base) => variants.get(base),
+            None => return,
+        };
+
+        self.selector = match self.selector {
+            None => selectors.first().copied(),
+            Some(selector) => selectors.iter().skip_while(|s| **s!=selector).nth(1).copied(),
+        };
+    }
+
+    pub fn selector(&self) -> String {
+        self.selector.map(String::from).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cycle_through_selectors() {
+        let mut variants = Variants::default();
+        variants.parse("5207 FE00; CJK COMPATIBILITY IDEOGRAPH-FA00;\n5207 FE01; CJK COMPATIBILITY IDEOGRAPH-2F850;");
+
+        let mut variant = Variant::new("切".to_string(), true);
+        variant.cycle(&variants);
+        assert_eq!(variant.selector, Some('\u{FE00}'));
+        variant.cycle(&variants);
+        assert_eq!(variant.selector, Some('\u{FE01}'));
+        variant.cycle(&variants);
+        assert_eq!(variant.selector, None);
+    }
+}
@@ -0,0 +1,119 @@
+mod shared;
+
+use benten::{ Function, BentenResponse };
+use shared::{ engine_with, press_keys, test_input_impl };
+
+const LAYOUT: &str = r#"{
+	current_method: Latin,
+	methods: [
+		{
+			id: Latin,
+			kind: Layout,
+			bindings: { 47: [ ChangeMethodTo(Kanji) ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ] },
+		},
+		{
+			id: Kanji,
+			kind: Table,
+			bindings: {
+				47: [ ChangeMethodTo(Latin) ],
+				49: [ CycleVariant ],
+				22: [ IfEmptyChangeMethodTo(Latin) ],
+			},
+			specs: { 22: [ BACKSPACE ], 65: [ COMMIT ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ], 39: [ b ] },
+		},
+	]
+}"#;
+
+const TABLE: (&str, &str) = ("Kanji", "㮝,a\n日,ab\n");
+
+fn change_method_to(method: &str) -> BentenResponse {
+    BentenResponse::Function(Function::ChangeMethodTo(method.to_string()))
+}
+
+fn suggest(value: &str) -> BentenResponse {
+    BentenResponse::Suggest(value.to_string())
+}
+
+#[test]
+fn cycle_suggested_variant() {
+    test_input_impl(engine_with("bindings-cycle", LAYOUT, &[TABLE]), &[
+        (47, change_method_to("Kanji")),
+        (38, suggest("㮝")),
+        (49, suggest("㮝\u{fe00}")),
+        (49, suggest("㮝\u{fe01}")),
+        (49, suggest("㮝")),
+        (49, suggest("㮝\u{fe00}")),
+        (65, BentenResponse::Commit("㮝\u{fe00}".to_string())),
+    ])
+}
+
+#[test]
+fn switch_drops_suggestion() {
+    let mut engine = engine_with("bindings-switch", LAYOUT, &[TABLE]);
+    press_keys(&mut engine, &[
+        (47, change_method_to("Kanji")),
+        (38, suggest("㮝")),
+        (47, change_method_to("Latin")),
+    ]);
+
+    assert!(engine.is_empty());
+    press_keys(&mut engine, &[(38, BentenResponse::Commit("a".to_string()))]);
+}
+
+#[test]
+fn if_empty_before_key() {
+    test_input_impl(engine_with("bindings-if-empty", LAYOUT, &[TABLE]), &[
+        (47, change_method_to("Kanji")),
+        (38, suggest("㮝")),
+        (39, suggest("日")),
+        (22, suggest("㮝")),
+
+        //deleting the last key stays in the method, another backspace leaves it
+        (22, BentenResponse::Empty),
+        (22, BentenResponse::Function(Function::IfEmptyChangeMethodTo("Latin".to_string()))),
+        (38, BentenResponse::Commit("a".to_string())),
+    ])
+}
+
+#[test]
+fn one_shot_in_current_method() {
+    const LAYOUT: &str = r#"{
+	current_method: Latin,
+	methods: [
+		{
+			id: Latin,
+			kind: Layout,
+			bindings: { 47: [ ChangeMethodTo(Greek) ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ] },
+		},
+		{
+			id: Greek,
+			kind: Layout,
+			bindings: { 47: [ OneShot(Greek) ], 48: [ ReturnToPreviousMethod ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ α ] },
+		},
+	]
+}"#;
+
+    let mut engine = engine_with("bindings-one-shot", LAYOUT, &[]);
+    press_keys(&mut engine, &[
+        (47, change_method_to("Greek")),
+        (47, BentenResponse::Function(Function::OneShot("Greek".to_string()))),
+        (38, BentenResponse::Commit("α".to_string())),
+    ]);
+
+    //still switched to with `ChangeMethodTo`, from which it returns as usual
+    assert_eq!(engine.current_method(), "Greek");
+    press_keys(&mut engine, &[(48, BentenResponse::Function(Function::ReturnToPreviousMethod))]);
+    assert_eq!(engine.current_method(), "Latin");
+}
@@ -9,7 +9,8 @@ define_layout_test!("japanese");
 fn kana_level_1() {
     test_input(&[
         (21, BentenResponse::Commit("み".to_string())),
-        (25, BentenResponse::Commit("く".to_string()))
+        //held as it composes with a dakuten
+        (25, BentenResponse::Suggest("く".to_string()))
     ])
 }
 
@@ -46,7 +47,8 @@ fn kana_level_5() {
     test_input(&[
         (66, BentenResponse::Undefined),
         (21, BentenResponse::Commit("ミ".to_string())),
-        (25, BentenResponse::Commit("ク".to_string()))
+        (25, BentenResponse::Commit("ク".to_string())),
+        (34, BentenResponse::Undefined)
     ])
 }
 
@@ -56,27 +58,28 @@ fn kana_level_6() {
         (66, BentenResponse::Undefined),
         (50, BentenResponse::Undefined),
         (21, BentenResponse::Undefined),
-        (25, BentenResponse::Undefined)
+        (25, BentenResponse::Undefined),
+        (41, BentenResponse::Commit("𛄢".to_string()))
     ])
 }
 
 #[test]
 fn cangjie_commit_key() {
     test_input(&[
-        (47, BentenResponse::Function(Function::ChangeMethodTo("cangjie5".to_string()))),
+        (47, BentenResponse::Function(Function::OneShot("Cangjie5".to_string()))),
         (38, BentenResponse::Suggest(String::from("日"))),
         (65, BentenResponse::Commit(String::from("日"))),
 
         //commit then change method to kana
-        (25, BentenResponse::Commit(String::from("く"))),
-        (65, BentenResponse::Undefined)
+        (25, BentenResponse::Suggest(String::from("く"))),
+        (65, BentenResponse::CommitThen(String::from("く"), Box::new(BentenResponse::Undefined)))
     ])
 }
 
 #[test]
 fn cangjie_next_prev_key() {
     test_input(&[
-        (47, BentenResponse::Function(Function::ChangeMethodTo("cangjie5".to_string()))),
+        (47, BentenResponse::Function(Function::OneShot("Cangjie5".to_string()))),
         (38, BentenResponse::Suggest(String::from("日"))),
         (23, BentenResponse::Suggest(String::from("曰"))),
         (65, BentenResponse::Commit(String::from("曰")))
@@ -86,26 +89,27 @@ fn cangjie_next_prev_key() {
 #[test]
 fn cangjie_backspace_key() {
     test_input(&[
-        (47, BentenResponse::Function(Function::ChangeMethodTo("cangjie5".to_string()))),
+        (47, BentenResponse::Function(Function::OneShot("Cangjie5".to_string()))),
         (24, BentenResponse::Suggest(String::from("手"))),
         (24, BentenResponse::Suggest(String::from("抙"))),
         (22, BentenResponse::Suggest(String::from("手"))),
         (65, BentenResponse::Commit(String::from("手"))),
 
-        (47, BentenResponse::Function(Function::ChangeMethodTo("cangjie5".to_string()))),
+        (47, BentenResponse::Function(Function::OneShot("Cangjie5".to_string()))),
         (24, BentenResponse::Suggest(String::from("手"))),
         (24, BentenResponse::Suggest(String::from("抙"))),
         (22, BentenResponse::Suggest(String::from("手"))),
-        (22, BentenResponse::Function(Function::ChangeMethodTo("kana".to_string()))),
+        (22, BentenResponse::Empty),
+        (22, BentenResponse::Function(Function::IfEmptyReturnToPreviousMethod)),
         // //swap back to kana when empty
-        (25, BentenResponse::Commit(String::from("く"))),
+        (25, BentenResponse::Suggest(String::from("く"))),
     ])
 }
 
 #[test]
 fn cangjie_on_no_result() {
     test_input(&[
-        (47, BentenResponse::Function(Function::ChangeMethodTo("cangjie5".to_string()))),
+        (47, BentenResponse::Function(Function::OneShot("Cangjie5".to_string()))),
         (24, BentenResponse::Suggest(String::from("手"))),
         (24, BentenResponse::Suggest(String::from("抙"))),
         (24, BentenResponse::Suggest(String::from("掱"))),
@@ -117,4 +121,29 @@ fn cangjie_on_no_result() {
         (24, BentenResponse::Suggest(String::from("掱"))),
         (65, BentenResponse::Commit(String::from("掱"))),
     ])
-}
\ No newline at end of file
+}
+
+#[test]
+fn kana_dakuten_compose() {
+    test_input(&[
+        (39, BentenResponse::Suggest("か".to_string())),
+        (34, BentenResponse::Commit("が".to_string())),
+
+        //held character is committed when the next key does not compose with it
+        (41, BentenResponse::Suggest("は".to_string())),
+        (21, BentenResponse::CommitThen("は".to_string(), Box::new(BentenResponse::Commit("み".to_string())))),
+        (34, BentenResponse::Commit("゙".to_string()))
+    ])
+}
+
+#[test]
+fn one_shot_with_held_kana() {
+    test_input(&[
+        //the held kana is committed, the one-shot method staying in use until its own commit
+        (41, BentenResponse::Suggest("は".to_string())),
+        (47, BentenResponse::CommitThen("は".to_string(), Box::new(BentenResponse::Function(Function::OneShot("Cangjie5".to_string()))))),
+        (38, BentenResponse::Suggest("日".to_string())),
+        (65, BentenResponse::Commit("日".to_string())),
+        (21, BentenResponse::Commit("み".to_string())),
+    ])
+}
@@ -0,0 +1,32 @@
+#[macro_use]
+mod shared;
+
+use benten::BentenResponse;
+
+define_layout_test!("katakana-lock");
+
+#[test]
+fn locked_katakana() {
+    test_input(&[
+        (66, BentenResponse::Undefined),
+        (21, BentenResponse::Commit("ミ".to_string())),
+        (50, BentenResponse::Undefined),
+        (38, BentenResponse::Commit("ヱ".to_string())),
+    ])
+}
+
+#[test]
+fn locked_dakuten_compose() {
+    test_input(&[
+        (66, BentenResponse::Undefined),
+        (39, BentenResponse::Suggest("カ".to_string())),
+        (34, BentenResponse::Commit("ガ".to_string())),
+        (41, BentenResponse::Suggest("ハ".to_string())),
+        (35, BentenResponse::Commit("パ".to_string())),
+
+        //unlocked again
+        (66, BentenResponse::Undefined),
+        (39, BentenResponse::Suggest("か".to_string())),
+        (34, BentenResponse::Commit("が".to_string())),
+    ])
+}
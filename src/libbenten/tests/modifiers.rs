@@ -0,0 +1,61 @@
+mod shared;
+
+use benten::BentenResponse;
+use shared::{ engine_with, press_keys };
+
+const LAYOUT: &str = r#"{
+	current_method: Latin,
+	methods: [
+		{
+			id: Latin,
+			kind: Layout,
+			bindings: { 49: [ Reset ] },
+			modifiers: [
+				{ kind: Lock, key_codes: [ 66 ] },
+				{ kind: Latch, key_codes: [ 50 ] },
+			],
+			levels: { 1: [], 2: [ 0 ], 3: [ 1 ] },
+			keys: { 38: [ a, A, á ] },
+		},
+	]
+}"#;
+
+fn commit(value: &str) -> BentenResponse {
+    BentenResponse::Commit(value.to_string())
+}
+
+#[test]
+fn locks_kept_on_reset() {
+    let mut engine = engine_with("modifiers-reset", LAYOUT, &[]);
+    press_keys(&mut engine, &[
+        (66, BentenResponse::Undefined),
+        (38, commit("A")),
+    ]);
+
+    //as frontends do on every commit
+    engine.reset();
+    press_keys(&mut engine, &[(38, commit("A"))]);
+
+    //released along with every other modifier
+    engine.clear();
+    press_keys(&mut engine, &[(38, commit("a"))]);
+}
+
+#[test]
+fn latch_locked_on_reset() {
+    let mut engine = engine_with("modifiers-latch", LAYOUT, &[]);
+    press_keys(&mut engine, &[
+        (50, BentenResponse::Undefined),
+        (50, BentenResponse::Undefined),
+        (38, commit("á")),
+    ]);
+
+    engine.reset();
+    press_keys(&mut engine, &[
+        (38, commit("á")),
+
+        //the binding releases them
+        (49, BentenResponse::Empty),
+        (38, commit("a")),
+    ]);
+}
@@ -0,0 +1,34 @@
+mod shared;
+
+use benten::{ BentenEngine, BentenConfig };
+use shared::engine_with;
+
+const LAYOUT: &str = r#"{
+	current_method: Latin,
+	methods: [
+		{
+			id: Latin,
+			kind: Layout,
+			bindings: { 47: [ NextLayout ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ] },
+		},
+	]
+}"#;
+
+#[test]
+fn malformed_layout_skipped() {
+    engine_with("preload", LAYOUT, &[]);
+    let dir = std::env::temp_dir().join("benten-tests").join("preload");
+    std::fs::write(dir.join("layouts").join("broken.layout.zm"), "{ current_method: Latin, methods: [ {").unwrap();
+
+    let mut engine = BentenEngine::new(BentenConfig {
+        id: "preload".to_string(),
+        dir,
+        preload: vec!["preload".to_string(), "broken".to_string()],
+    });
+
+    engine.on_key_press(47);
+    assert_eq!(engine.layout(), "preload");
+}
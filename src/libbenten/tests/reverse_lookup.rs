@@ -0,0 +1,41 @@
+mod shared;
+
+use benten::{ Function, BentenResponse };
+use shared::{ engine_with, test_input_impl };
+
+const LAYOUT: &str = r#"{
+	current_method: Kana,
+	reverse_lookup: Hints,
+	methods: [
+		{
+			id: Kana,
+			kind: Layout,
+			bindings: { 47: [ ChangeMethodTo(Hints) ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ 日 ], 39: [ か ] },
+		},
+		{
+			id: Hints,
+			kind: Table,
+			bindings: { 47: [ ChangeMethodTo(Kana) ] },
+			specs: { 65: [ COMMIT ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ], 39: [ b ] },
+		},
+	]
+}"#;
+
+#[test]
+fn hint_after_commit() {
+    test_input_impl(engine_with("reverse-lookup", LAYOUT, &[("Hints", "日,a\n日,ab\n明,b\n")]), &[
+        (38, BentenResponse::CommitThen("日".to_string(), Box::new(BentenResponse::Hint(String::new(), "a ab".to_string())))),
+        (39, BentenResponse::Commit("か".to_string())),
+
+        //no hint for what the reverse lookup method commits itself
+        (47, BentenResponse::Function(Function::ChangeMethodTo("Hints".to_string()))),
+        (39, BentenResponse::Suggest("明".to_string())),
+        (65, BentenResponse::Commit("明".to_string())),
+    ])
+}
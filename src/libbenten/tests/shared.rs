@@ -1,14 +1,42 @@
-use benten::{ BentenEngine, BentenResponse };
+use benten::{ BentenEngine, BentenConfig, BentenResponse };
 
+#[allow(dead_code)]
 #[track_caller]
 pub fn test_input_impl(mut engine: BentenEngine, keys: &[(u16, BentenResponse)]) {
+    press_keys(&mut engine, keys);
+}
+
+/// Presses each key in turn, checking the response of the engine, which is kept for more keys
+#[allow(dead_code)]
+#[track_caller]
+pub fn press_keys(engine: &mut BentenEngine, keys: &[(u16, BentenResponse)]) {
     for (key, response) in keys.iter() {
         let rep = engine.on_key_press(key.to_owned());
-        eprintln!("Key: {:?}, Rep: {:?}", key, rep);
         assert_eq!(&rep, response);
     }
 }
 
+/// Engine of layout `id`, written along with the `tables` it uses to a directory of its own
+/// rather than read from the config, compiling and learning them in temporary directories as well
+#[allow(dead_code)]
+pub fn engine_with(id: &str, layout: &str, tables: &[(&str, &str)]) -> BentenEngine {
+    static DIRS: std::sync::Once = std::sync::Once::new();
+    DIRS.call_once(|| {
+        std::env::set_var("XDG_CACHE_HOME", std::env::temp_dir().join("benten-tests").join("cache"));
+        std::env::set_var("XDG_DATA_HOME", std::env::temp_dir().join("benten-tests").join("data"));
+    });
+
+    let dir = std::env::temp_dir().join("benten-tests").join(id);
+    std::fs::create_dir_all(dir.join("layouts")).unwrap();
+    std::fs::create_dir_all(dir.join("tables")).unwrap();
+    std::fs::write(dir.join("layouts").join(id).with_extension("layout.zm"), layout).unwrap();
+    for (table, entries) in tables {
+        std::fs::write(dir.join("tables").join(table).with_extension("dict"), format!("character,sequence\n{}", entries)).unwrap();
+    }
+
+    BentenEngine::new(BentenConfig { id: id.to_string(), dir, preload: Vec::new() })
+}
+
 #[allow(unused_macros)]
 macro_rules! define_layout_test {
     ($layout:expr) => {
@@ -0,0 +1,43 @@
+mod shared;
+
+use benten::BentenResponse;
+use shared::{ engine_with, press_keys };
+
+const LAYOUT: &str = r#"{
+	current_method: Kanji,
+	methods: [
+		{
+			id: Kanji,
+			kind: Table,
+			bindings: { 49: [ CycleVariant ] },
+			specs: { 65: [ COMMIT ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ] },
+		},
+	]
+}"#;
+
+fn replace(previous: &str, selector: &str) -> BentenResponse {
+    BentenResponse::Replace(previous.to_string(), selector.to_string())
+}
+
+#[test]
+fn cycle_committed_after_reset() {
+    let mut engine = engine_with("variants", LAYOUT, &[("Kanji", "㮝,a\n")]);
+    press_keys(&mut engine, &[
+        (38, BentenResponse::Suggest("㮝".to_string())),
+        (65, BentenResponse::Commit("㮝".to_string())),
+    ]);
+
+    //as frontends do on every commit
+    engine.reset();
+    press_keys(&mut engine, &[
+        (49, replace("", "\u{fe00}")),
+        (49, replace("\u{fe00}", "\u{fe01}")),
+
+        //forgotten once another key is typed
+        (10, BentenResponse::Empty),
+        (49, BentenResponse::Empty),
+    ]);
+}
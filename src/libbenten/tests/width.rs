@@ -0,0 +1,78 @@
+mod shared;
+
+use benten::{ Function, BentenResponse, Width };
+use shared::{ engine_with, test_input_impl };
+
+const LAYOUT: &str = r#"{
+	current_method: Latin,
+	methods: [
+		{
+			id: Latin,
+			kind: Layout,
+			bindings: { 49: [ ToggleWidth(Fullwidth) ], 47: [ ChangeMethodTo(Wide) ] },
+			compose: { "'": { "a": "á" } },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ], 39: [ b ], 48: [ "'" ] },
+		},
+		{
+			id: Wide,
+			kind: Table,
+			width: Fullwidth,
+			bindings: { 49: [ ToggleWidth(Fullwidth) ], 47: [ ChangeMethodTo(Latin) ] },
+			specs: { 23: [ WILDCARD ], 65: [ COMMIT ] },
+			modifiers: [],
+			levels: { 1: [] },
+			keys: { 38: [ a ], 39: [ b ] },
+		},
+	]
+}"#;
+
+fn toggle() -> BentenResponse {
+    BentenResponse::Function(Function::ToggleWidth(Width::Fullwidth))
+}
+
+fn change_method_to(method: &str) -> BentenResponse {
+    BentenResponse::Function(Function::ChangeMethodTo(method.to_string()))
+}
+
+#[test]
+fn toggle_width() {
+    test_input_impl(engine_with("width", LAYOUT, &[("Wide", "12,ab\n3,b\n")]), &[
+        (39, BentenResponse::Commit("b".to_string())),
+        (49, toggle()),
+        (39, BentenResponse::Commit("ｂ".to_string())),
+
+        //held character committed along with the next one
+        (38, BentenResponse::Suggest("ａ".to_string())),
+        (39, BentenResponse::CommitThen("ａ".to_string(), Box::new(BentenResponse::Commit("ｂ".to_string())))),
+
+        (49, toggle()),
+        (39, BentenResponse::Commit("b".to_string())),
+    ])
+}
+
+#[test]
+fn toggle_method_width() {
+    test_input_impl(engine_with("method-width", LAYOUT, &[("Wide", "12,ab\n3,b\n")]), &[
+        (47, change_method_to("Wide")),
+        (38, BentenResponse::Suggest("１２".to_string())),
+        (65, BentenResponse::Commit("１２".to_string())),
+
+        //hints are left as they are
+        (23, BentenResponse::Hint("３".to_string(), "b".to_string())),
+        (65, BentenResponse::Commit("３".to_string())),
+
+        //the toggle overrides the width of the method, the table taking the key as it has nothing typed
+        (49, BentenResponse::Empty),
+        (38, BentenResponse::Suggest("12".to_string())),
+        (65, BentenResponse::Commit("12".to_string())),
+        (49, BentenResponse::Empty),
+        (38, BentenResponse::Suggest("１２".to_string())),
+        (65, BentenResponse::Commit("１２".to_string())),
+
+        //while other methods keep their own
+        (47, change_method_to("Latin")),
+        (39, BentenResponse::Commit("b".to_string())),
+    ])
+}